@@ -0,0 +1,148 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use twitch_irc::message::ServerMessage;
+
+use crate::error::{Error, Result};
+use crate::irc::{ChatMessage, ComponentMessage, IrcEvent, MessageDispatcher};
+
+// how often announcements are checked for being due
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+// longest interval between posts, a week
+const MAX_INTERVAL_MINUTES: u64 = 7 * 24 * 60;
+
+/// A set of messages posted in rotation to a channel, eg:
+///
+/// ```yaml
+/// - channel: uuayn
+///   interval_minutes: 15
+///   min_messages: 10
+///   messages:
+///     - "follow the socials!"
+///     - "type !project for the repo"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct Announcement {
+    pub channel: String,
+    /// Minimum time between two posts, at least a minute and at most a week.
+    pub interval_minutes: u64,
+    /// Minimum number of chat messages since the last post, so the bot doesn't talk to an empty
+    /// room.
+    #[serde(default)]
+    pub min_messages: u64,
+    pub messages: Vec<String>,
+}
+
+struct AnnouncementState {
+    announcement: Announcement,
+    last_posted: Instant,
+    chat_messages: u64,
+    next_message: usize,
+}
+
+impl AnnouncementState {
+    fn is_due(&self, now: Instant) -> bool {
+        let interval = Duration::from_secs(self.announcement.interval_minutes.saturating_mul(60));
+        !self.announcement.messages.is_empty()
+            && now.duration_since(self.last_posted) >= interval
+            && self.chat_messages >= self.announcement.min_messages
+    }
+}
+
+pub struct Announcer {
+    dispatcher: MessageDispatcher,
+    announcements: Vec<AnnouncementState>,
+}
+
+impl Announcer {
+    pub fn new(dispatcher: MessageDispatcher, announcements: Vec<Announcement>) -> Self {
+        let now = Instant::now();
+        Self {
+            dispatcher,
+            announcements: announcements
+                .into_iter()
+                .map(|announcement| AnnouncementState {
+                    announcement,
+                    last_posted: now,
+                    chat_messages: 0,
+                    next_message: 0,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(dispatcher: MessageDispatcher, path: P) -> Result<Self> {
+        Ok(Self::new(dispatcher, load_announcements(path)?))
+    }
+
+    pub async fn run(&mut self) {
+        let mut check_interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                message = self.dispatcher.recv() => match message {
+                    Some(IrcEvent::Message(ServerMessage::Privmsg(msg))) => {
+                        for state in self.announcements.iter_mut() {
+                            if state.announcement.channel == msg.channel_login {
+                                state.chat_messages += 1;
+                            }
+                        }
+                    }
+                    Some(_) => continue,
+                    None => break,
+                },
+                _ = check_interval.tick() => self.post_due().await,
+            }
+        }
+    }
+
+    async fn post_due(&mut self) {
+        let now = Instant::now();
+        for state in self.announcements.iter_mut() {
+            if !state.is_due(now) {
+                continue;
+            }
+            let messages = &state.announcement.messages;
+            let message = messages[state.next_message % messages.len()].clone();
+            if let Err(e) = self
+                .dispatcher
+                .send(ComponentMessage::Announcement(ChatMessage {
+                    channel: state.announcement.channel.clone(),
+                    message,
+                }))
+                .await
+            {
+                println!("failed to send announcement: {}", e);
+            }
+            state.next_message = (state.next_message + 1) % messages.len();
+            state.last_posted = now;
+            state.chat_messages = 0;
+        }
+    }
+}
+
+/// Load and validate the announcements in the YAML file at `path`.
+pub fn load_announcements<P: AsRef<Path>>(path: P) -> Result<Vec<Announcement>> {
+    let mut file = File::open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let announcements: Vec<Announcement> = serde_yaml::from_str(&contents)?;
+    for announcement in announcements.iter() {
+        // would post on every check, flooding the channel
+        if announcement.interval_minutes == 0 {
+            return Err(Error::InvalidConfig(format!(
+                "announcement for channel \"{}\" has an interval_minutes of 0, expected at least 1",
+                announcement.channel
+            )));
+        }
+        if announcement.interval_minutes > MAX_INTERVAL_MINUTES {
+            return Err(Error::InvalidConfig(format!(
+                "announcement for channel \"{}\" has an interval_minutes of {}, expected at most {}",
+                announcement.channel, announcement.interval_minutes, MAX_INTERVAL_MINUTES
+            )));
+        }
+    }
+    Ok(announcements)
+}
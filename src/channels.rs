@@ -0,0 +1,45 @@
+use std::collections::BTreeSet;
+
+use tokio::sync::watch;
+use twitch_irc::message::ServerMessage;
+
+use crate::irc::{ComponentMessage, IrcEvent, JoinChannelMessage, MessageDispatcher};
+
+/// Keeps the bot in the channels it should be in, see `ChannelCommander`, by joining them every
+/// time it logs in to Twitch, including after a reconnect.
+pub struct ChannelManager {
+    dispatcher: MessageDispatcher,
+    channels: watch::Receiver<BTreeSet<String>>,
+}
+
+impl ChannelManager {
+    pub fn new(dispatcher: MessageDispatcher, channels: watch::Receiver<BTreeSet<String>>) -> Self {
+        Self {
+            dispatcher,
+            channels,
+        }
+    }
+
+    pub async fn run(&mut self) {
+        while let Some(message) = self.dispatcher.recv().await {
+            match message {
+                // Twitch sends GLOBALUSERSTATE once the bot has logged in on a connection
+                IrcEvent::Message(ServerMessage::GlobalUserState(_)) => self.join_all().await,
+                _ => continue,
+            }
+        }
+    }
+
+    async fn join_all(&self) {
+        let channels = self.channels.borrow().clone();
+        println!("joining {} channels", channels.len());
+        for channel in channels.into_iter() {
+            let message = ComponentMessage::JoinChannel(JoinChannelMessage {
+                channel: channel.clone(),
+            });
+            if let Err(e) = self.dispatcher.send(message).await {
+                println!("failed to join {} channel: {}", channel, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,455 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// What a command parameter is parsed as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArgKind {
+    /// A single word, or several words in double quotes.
+    Word,
+    /// A chatter, given as `login`, `@login` or `@login,`, normalized to the lowercase login.
+    User,
+    Integer,
+    /// Seconds, or a combination of days, hours, minutes and seconds, eg `90`, `10m`, `1h30m`.
+    Duration,
+    /// The remainder of the arguments as they were written. Must be the last parameter.
+    Rest,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArgValue {
+    Text(String),
+    User(String),
+    Integer(i64),
+    Duration(Duration),
+}
+
+#[derive(Clone, Debug)]
+struct Param {
+    name: String,
+    kind: ArgKind,
+    required: bool,
+}
+
+/// The parameters a command takes, used to parse its arguments and to describe its usage, eg:
+///
+/// ```ignore
+/// let signature = Signature::new("timeout")
+///     .required("user", ArgKind::User)
+///     .optional("duration", ArgKind::Duration)
+///     .optional("reason", ArgKind::Rest);
+/// assert_eq!(signature.usage("!"), "!timeout <user> [duration] [reason...]");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Signature {
+    command: String,
+    params: Vec<Param>,
+}
+
+impl Signature {
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            params: Vec::new(),
+        }
+    }
+
+    pub fn required(mut self, name: &str, kind: ArgKind) -> Self {
+        self.params.push(Param {
+            name: name.to_string(),
+            kind,
+            required: true,
+        });
+        self
+    }
+
+    /// Add a parameter that may be left out. Only other optional parameters may follow it.
+    pub fn optional(mut self, name: &str, kind: ArgKind) -> Self {
+        self.params.push(Param {
+            name: name.to_string(),
+            kind,
+            required: false,
+        });
+        self
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// How the command is invoked, eg `!addquote <author> <text...>`.
+    pub fn usage(&self, prefix: &str) -> String {
+        let mut usage = format!("{}{}", prefix, self.command);
+        for param in self.params.iter() {
+            let name = match param.kind {
+                ArgKind::Rest => format!("{}...", param.name),
+                _ => param.name.clone(),
+            };
+            if param.required {
+                usage.push_str(&format!(" <{}>", name));
+            } else {
+                usage.push_str(&format!(" [{}]", name));
+            }
+        }
+        usage
+    }
+
+    /// Parse `args` according to this signature, `prefix` being what the command was invoked
+    /// with for the usage in errors.
+    pub fn parse(&self, prefix: &str, args: &str) -> Result<Args, UsageError> {
+        let usage_error = |reason: String| UsageError {
+            reason,
+            usage: self.usage(prefix),
+        };
+
+        let mut values = HashMap::new();
+        // byte offset of the arguments not parsed yet
+        let mut parsed = 0;
+        for param in self.params.iter() {
+            if param.kind == ArgKind::Rest {
+                // taken as written rather than tokenized, so a stray quote is just a quote
+                let rest = args[parsed..].trim();
+                if rest.is_empty() {
+                    if param.required {
+                        return Err(usage_error(format!("missing {}", param.name)));
+                    }
+                    break;
+                }
+                values.insert(param.name.clone(), ArgValue::Text(unquote(rest)));
+                return Ok(Args { values });
+            }
+
+            let token = match next_token(args, parsed).map_err(usage_error)? {
+                Some((token, end)) => {
+                    parsed = end;
+                    token
+                }
+                None if param.required => {
+                    return Err(usage_error(format!("missing {}", param.name)))
+                }
+                None => break,
+            };
+            let value = match param.kind {
+                ArgKind::Rest => unreachable!("the remainder is taken before tokenizing"),
+                ArgKind::Word => ArgValue::Text(token.text.clone()),
+                ArgKind::User => match normalize_login(&token.text) {
+                    Some(login) => ArgValue::User(login),
+                    None => {
+                        return Err(usage_error(format!(
+                            "{} isn't a valid {}",
+                            token.text, param.name
+                        )))
+                    }
+                },
+                ArgKind::Integer => match token.text.parse() {
+                    Ok(n) => ArgValue::Integer(n),
+                    Err(_) => {
+                        return Err(usage_error(format!(
+                            "{} must be a number, not {}",
+                            param.name, token.text
+                        )))
+                    }
+                },
+                ArgKind::Duration => match parse_duration(&token.text) {
+                    Some(duration) => ArgValue::Duration(duration),
+                    None => {
+                        return Err(usage_error(format!(
+                            "{} must be a duration like 90, 10m or 1h30m, not {}",
+                            param.name, token.text
+                        )))
+                    }
+                },
+            };
+            values.insert(param.name.clone(), value);
+        }
+        if let Some((token, _)) = next_token(args, parsed).map_err(usage_error)? {
+            return Err(usage_error(format!("unexpected {}", token.text)));
+        }
+        Ok(Args { values })
+    }
+}
+
+/// Arguments parsed according to a `Signature`, by parameter name.
+#[derive(Clone, Debug, Default)]
+pub struct Args {
+    values: HashMap<String, ArgValue>,
+}
+
+impl Args {
+    pub fn get(&self, name: &str) -> Option<&ArgValue> {
+        self.values.get(name)
+    }
+
+    /// A `Word` or `Rest` argument.
+    pub fn text(&self, name: &str) -> Option<&str> {
+        match self.values.get(name)? {
+            ArgValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn user(&self, name: &str) -> Option<&str> {
+        match self.values.get(name)? {
+            ArgValue::User(login) => Some(login),
+            _ => None,
+        }
+    }
+
+    pub fn integer(&self, name: &str) -> Option<i64> {
+        match self.values.get(name)? {
+            ArgValue::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn duration(&self, name: &str) -> Option<Duration> {
+        match self.values.get(name)? {
+            ArgValue::Duration(duration) => Some(*duration),
+            _ => None,
+        }
+    }
+}
+
+/// Arguments that don't fit a command's `Signature`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsageError {
+    pub reason: String,
+    pub usage: String,
+}
+
+impl std::fmt::Display for UsageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, usage: {}", self.reason, self.usage)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// A whitespace separated argument, with surrounding quotes removed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    /// Byte offset of the token in the arguments, including any opening quote.
+    pub start: usize,
+}
+
+/// Split arguments at whitespace, keeping words in double quotes together. Within quotes, `\"`
+/// is a literal quote.
+pub fn tokenize(args: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut parsed = 0;
+    while let Some((token, end)) = next_token(args, parsed)? {
+        tokens.push(token);
+        parsed = end;
+    }
+    Ok(tokens)
+}
+
+/// The first token in `args` at or after byte offset `from`, along with the offset just past it.
+fn next_token(args: &str, from: usize) -> Result<Option<(Token, usize)>, String> {
+    let mut chars = args[from..]
+        .char_indices()
+        .map(|(index, c)| (from + index, c))
+        .skip_while(|&(_, c)| c.is_whitespace())
+        .peekable();
+    let start = match chars.peek() {
+        Some(&(start, _)) => start,
+        None => return Ok(None),
+    };
+
+    let mut text = String::new();
+    if args[start..].starts_with('"') {
+        chars.next();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => return Ok(Some((Token { text, start }, index + 1))),
+                '\\' if chars.peek().map(|&(_, c)| c) == Some('"') => {
+                    text.push('"');
+                    chars.next();
+                }
+                c => text.push(c),
+            }
+        }
+        return Err("unclosed quote".to_string());
+    }
+    for (index, c) in chars {
+        if c.is_whitespace() {
+            return Ok(Some((Token { text, start }, index)));
+        }
+        text.push(c);
+    }
+    Ok(Some((Token { text, start }, args.len())))
+}
+
+/// `rest` without its quotes if it's a single quoted token, eg `"hi there"`, else as it is.
+fn unquote(rest: &str) -> String {
+    match next_token(rest, 0) {
+        Ok(Some((token, end))) if rest.starts_with('"') && end == rest.len() => token.text,
+        _ => rest.to_string(),
+    }
+}
+
+/// The lowercase login of a chatter mentioned as `login`, `@login` or `@login,`, if valid.
+pub fn normalize_login(mention: &str) -> Option<String> {
+    let login = mention
+        .trim_start_matches('@')
+        .trim_end_matches([',', ':', '.', '!', '?']);
+    if login.is_empty() || !login.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some(login.to_lowercase())
+}
+
+/// Whether `name` may name a command defined from chat, eg with `!addcom`: letters, digits and
+/// underscores only.
+pub fn is_command_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Parse seconds (`90`) or a combination of `d`, `h`, `m` and `s` units (`1h30m`).
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    if let Ok(seconds) = text.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            'd' => 24 * 60 * 60,
+            'h' => 60 * 60,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        let n: u64 = number.parse().ok()?;
+        seconds = seconds.checked_add(n.checked_mul(unit)?)?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return None;
+    }
+    Some(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(args: &str) -> Result<Vec<String>, String> {
+        Ok(tokenize(args)?
+            .into_iter()
+            .map(|token| token.text)
+            .collect())
+    }
+
+    #[test]
+    fn tokenize_words_and_quotes() {
+        assert_eq!(texts("  a b\tc ").unwrap(), vec!["a", "b", "c"]);
+        assert_eq!(
+            texts(r#"one "two three" four"#).unwrap(),
+            vec!["one", "two three", "four"]
+        );
+        assert_eq!(
+            texts(r#""say \"hi\"" now"#).unwrap(),
+            vec![r#"say "hi""#, "now"]
+        );
+        assert_eq!(texts(r#""""#).unwrap(), vec![""]);
+        assert!(texts("").unwrap().is_empty());
+        assert!(texts(r#"an "unclosed quote"#).is_err());
+    }
+
+    #[test]
+    fn tokenize_offsets() {
+        let tokens = tokenize(r#"ab "c d" e"#).unwrap();
+        let starts: Vec<usize> = tokens.iter().map(|token| token.start).collect();
+        assert_eq!(starts, vec![0, 3, 9]);
+    }
+
+    #[test]
+    fn rest_is_taken_as_written() {
+        let signature = Signature::new("addcom")
+            .required("name", ArgKind::Word)
+            .required("response", ArgKind::Rest);
+        let args = signature.parse("!", r#" height 6'2" "#).unwrap();
+        assert_eq!(args.text("name"), Some("height"));
+        assert_eq!(args.text("response"), Some(r#"6'2""#));
+
+        let args = signature.parse("!", r#"quote he said "hi"#).unwrap();
+        assert_eq!(args.text("response"), Some(r#"he said "hi"#));
+
+        let args = signature.parse("!", r#"greet "hello there""#).unwrap();
+        assert_eq!(args.text("response"), Some("hello there"));
+
+        let args = signature.parse("!", r#"greet "hello" "there""#).unwrap();
+        assert_eq!(args.text("response"), Some(r#""hello" "there""#));
+
+        let error = signature.parse("!", "greet").unwrap_err();
+        assert_eq!(error.reason, "missing response");
+    }
+
+    #[test]
+    fn parse_typed_params() {
+        let signature = Signature::new("timeout")
+            .required("user", ArgKind::User)
+            .optional("duration", ArgKind::Duration)
+            .optional("reason", ArgKind::Rest);
+        assert_eq!(
+            signature.usage("!"),
+            "!timeout <user> [duration] [reason...]"
+        );
+
+        let args = signature.parse("!", "@Viewer, 10m spam").unwrap();
+        assert_eq!(args.user("user"), Some("viewer"));
+        assert_eq!(args.duration("duration"), Some(Duration::from_secs(600)));
+        assert_eq!(args.text("reason"), Some("spam"));
+
+        let args = signature.parse("!", "viewer").unwrap();
+        assert_eq!(args.duration("duration"), None);
+        assert_eq!(args.text("reason"), None);
+
+        assert!(signature.parse("!", "").is_err());
+        assert!(signature.parse("!", "viewer soon").is_err());
+        assert!(Signature::new("quote")
+            .optional("id", ArgKind::Integer)
+            .parse("!", "12 13")
+            .is_err());
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("10m"), Some(Duration::from_secs(600)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1D2H3M4S"), Some(Duration::from_secs(93784)));
+        assert_eq!(parse_duration("0s"), Some(Duration::from_secs(0)));
+        assert_eq!(parse_duration("10"), Some(Duration::from_secs(10)));
+        assert_eq!(parse_duration("1h30"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("5w"), None);
+        assert_eq!(parse_duration("-5s"), None);
+        assert_eq!(parse_duration("99999999999999999999d"), None);
+    }
+
+    #[test]
+    fn normalize_logins() {
+        assert_eq!(normalize_login("Viewer"), Some("viewer".to_string()));
+        assert_eq!(normalize_login("@Viewer,"), Some("viewer".to_string()));
+        assert_eq!(normalize_login("@some_one:"), Some("some_one".to_string()));
+        assert_eq!(normalize_login("@"), None);
+        assert_eq!(normalize_login("not-a-login"), None);
+        assert_eq!(normalize_login("ünicode"), None);
+    }
+
+    #[test]
+    fn command_names() {
+        assert!(is_command_name("meow"));
+        assert!(is_command_name("my_command2"));
+        assert!(!is_command_name(""));
+        assert!(!is_command_name("!"));
+        assert!(!is_command_name("two words"));
+        assert!(!is_command_name("dash-ed"));
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::BTreeSet;
+
+use tokio::sync::watch;
+
+use crate::commander::args::{ArgKind, Signature};
+use crate::commander::{CommandContext, CommandResponse, IrcCommander, Role};
+
+/// Channels the bot should be in, changed by the broadcaster from chat:
+///
+/// * `!join <channel>` - join a channel
+/// * `!part [channel]` - leave a channel, by default the one the command was invoked in
+///
+/// Only the broadcaster of the owner channel, see `with_owner_channel`, may join or leave other
+/// channels. Elsewhere `!part` only leaves the channel it's invoked in.
+///
+/// Changes are published to subscribers, eg the `ChannelManager` re-joining them after a
+/// reconnect, but not saved; the configured channels are joined again on restart.
+pub struct ChannelCommander {
+    channels: BTreeSet<String>,
+    sender: watch::Sender<BTreeSet<String>>,
+    owner_channel: Option<String>,
+}
+
+impl ChannelCommander {
+    pub fn new<I: IntoIterator<Item = String>>(channels: I) -> Self {
+        let channels: BTreeSet<String> = channels
+            .into_iter()
+            .map(|channel| channel.to_lowercase())
+            .collect();
+        let (sender, _) = watch::channel(channels.clone());
+        Self {
+            channels,
+            sender,
+            owner_channel: None,
+        }
+    }
+
+    /// Let the broadcaster of `channel`, eg the bot owner's own channel, make the bot join and
+    /// leave other channels.
+    pub fn with_owner_channel(mut self, channel: &str) -> Self {
+        self.owner_channel = Some(channel.to_lowercase());
+        self
+    }
+
+    fn in_owner_channel(&self, ctx: &CommandContext) -> bool {
+        self.owner_channel.as_deref() == Some(ctx.channel_login.as_str())
+    }
+
+    // reply to an attempt to join or leave another channel from outside the owner channel
+    fn refuse(&self, action: &str) -> Vec<CommandResponse> {
+        let reply = match &self.owner_channel {
+            Some(owner) => format!("other channels can only be {} from #{}", action, owner),
+            None => format!("other channels can't be {} from chat", action),
+        };
+        vec![CommandResponse::Reply(reply)]
+    }
+
+    /// Receive the channels the bot should be in whenever they change.
+    pub fn subscribe(&self) -> watch::Receiver<BTreeSet<String>> {
+        self.sender.subscribe()
+    }
+
+    fn join(&mut self, ctx: &CommandContext) -> Vec<CommandResponse> {
+        let channel = match ctx.parse_args(&join_signature()) {
+            Ok(args) => args.user("channel").unwrap_or_default().to_string(),
+            Err(e) => return vec![CommandResponse::Reply(e.to_string())],
+        };
+        if !self.in_owner_channel(ctx) {
+            return self.refuse("joined");
+        }
+        if !self.channels.insert(channel.clone()) {
+            return vec![CommandResponse::Reply(format!("already in #{}", channel))];
+        }
+        self.sender.send_replace(self.channels.clone());
+        vec![
+            CommandResponse::JoinChannel(channel.clone()),
+            CommandResponse::Reply(format!("joining #{}", channel)),
+        ]
+    }
+
+    fn part(&mut self, ctx: &CommandContext) -> Vec<CommandResponse> {
+        let channel = match ctx.parse_args(&part_signature()) {
+            Ok(args) => args
+                .user("channel")
+                .unwrap_or(&ctx.channel_login)
+                .to_string(),
+            Err(e) => return vec![CommandResponse::Reply(e.to_string())],
+        };
+        if channel != ctx.channel_login && !self.in_owner_channel(ctx) {
+            return self.refuse("left");
+        }
+        if !self.channels.remove(&channel) {
+            return vec![CommandResponse::Reply(format!("not in #{}", channel))];
+        }
+        self.sender.send_replace(self.channels.clone());
+        // reply first, it can't be sent to a channel that's already been left
+        vec![
+            CommandResponse::Reply(format!("leaving #{}", channel)),
+            CommandResponse::PartChannel(channel),
+        ]
+    }
+}
+
+impl IrcCommander for ChannelCommander {
+    fn name(&self) -> &'static str {
+        "channels"
+    }
+
+    fn get_commands(&self) -> Vec<String> {
+        vec!["join".to_string(), "part".to_string()]
+    }
+
+    fn required_role(&self, _cmd: &str) -> Role {
+        Role::Broadcaster
+    }
+
+    fn signature(&self, cmd: &str) -> Option<Signature> {
+        match cmd {
+            "join" => Some(join_signature()),
+            "part" => Some(part_signature()),
+            _ => None,
+        }
+    }
+
+    fn description(&self, cmd: &str) -> Option<String> {
+        match cmd {
+            "join" => Some("make the bot join a channel, from the owner's channel".to_string()),
+            "part" => Some(
+                "make the bot leave this channel, or another from the owner's channel".to_string(),
+            ),
+            _ => None,
+        }
+    }
+
+    fn handle_msg(&mut self, ctx: &CommandContext) -> Option<Vec<CommandResponse>> {
+        match ctx.command.as_str() {
+            "join" => Some(self.join(ctx)),
+            "part" => Some(self.part(ctx)),
+            _ => None,
+        }
+    }
+}
+
+fn join_signature() -> Signature {
+    Signature::new("join").required("channel", ArgKind::User)
+}
+
+fn part_signature() -> Signature {
+    Signature::new("part").optional("channel", ArgKind::User)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irc;
+
+    fn run(commander: &mut ChannelCommander, channel: &str, text: &str) -> Vec<String> {
+        let msg = irc::privmsg(channel, channel, text);
+        let (command, args) = text[1..].split_once(' ').unwrap_or((&text[1..], ""));
+        let ctx = CommandContext::new(&msg, "!", command, args);
+        commander
+            .handle_msg(&ctx)
+            .unwrap()
+            .into_iter()
+            .map(|response| match response {
+                CommandResponse::Reply(reply) => reply,
+                CommandResponse::JoinChannel(channel) => format!("JOIN #{}", channel),
+                CommandResponse::PartChannel(channel) => format!("PART #{}", channel),
+                response => panic!("unexpected response {:?}", response),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_the_owner_channel_joins_and_leaves_others() {
+        let channels = ["uuayn".to_string(), "someone".to_string()];
+        let mut commander = ChannelCommander::new(channels).with_owner_channel("uuayn");
+
+        assert_eq!(
+            run(&mut commander, "someone", "!join other"),
+            vec!["other channels can only be joined from #uuayn"]
+        );
+        assert_eq!(
+            run(&mut commander, "someone", "!part uuayn"),
+            vec!["other channels can only be left from #uuayn"]
+        );
+        assert_eq!(
+            run(&mut commander, "uuayn", "!join other"),
+            vec!["JOIN #other", "joining #other"]
+        );
+        assert_eq!(
+            run(&mut commander, "uuayn", "!part other"),
+            vec!["leaving #other", "PART #other"]
+        );
+        assert_eq!(
+            run(&mut commander, "someone", "!part"),
+            vec!["leaving #someone", "PART #someone"]
+        );
+        assert_eq!(
+            commander.subscribe().borrow().iter().collect::<Vec<_>>(),
+            vec!["uuayn"]
+        );
+    }
+}
@@ -0,0 +1,675 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use twitch_irc::message::{PrivmsgMessage, ServerMessage};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::commander::args::is_command_name;
+use crate::commander::{
+    AsyncIrcCommander, CommandContext, CommandRegistry, Cooldown, CooldownTracker, Reservation,
+    Role, Triggers,
+};
+use crate::irc::{
+    ChatMessage, CommandSender, ComponentMessage, IrcEvent, MessageDispatcher, ReplyMessage,
+    MAX_MESSAGE_LENGTH,
+};
+
+/// What `CommanderComposer` does when a chatter invokes a command they lack the role for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnauthorizedPolicy {
+    /// Pretend the command wasn't invoked.
+    Ignore,
+    /// Tell the chatter which role the command requires.
+    Refuse,
+}
+
+// how often watched command files are checked for modifications
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Prefix used in channels without configured prefixes.
+pub const DEFAULT_PREFIX: &str = "!";
+
+// commands handled by the composer itself rather than a commander
+const BUILTIN_COMMANDS: [&str; 2] = ["commands", "help"];
+
+// how long a single command handler may run before its response is discarded
+const DEFAULT_HANDLER_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct CommanderComposer {
+    commanders: Vec<Arc<dyn AsyncIrcCommander>>,
+    dispatcher: MessageDispatcher,
+    diagnostics_channel: Option<String>,
+    unauthorized_policy: UnauthorizedPolicy,
+    handler_timeout: Duration,
+    // per-command cooldowns that take precedence over those declared by commanders
+    cooldowns: HashMap<String, Cooldown>,
+    cooldown_tracker: Arc<Mutex<CooldownTracker>>,
+    triggers: Option<Triggers>,
+    // command prefixes of each channel, falling back to `default_prefixes`
+    prefixes: HashMap<String, Vec<String>>,
+    default_prefixes: Vec<String>,
+    // names of the commanders enabled in each channel, all of them in channels not listed
+    enabled_commanders: HashMap<String, Vec<String>>,
+    // lowercase login the bot can be addressed by instead of a prefix, eg "@botname help"
+    bot_login: Option<String>,
+    // last seen modification time of each watched file
+    watched_files: HashMap<PathBuf, SystemTime>,
+    registry: CommandRegistry,
+}
+
+impl CommanderComposer {
+    pub fn new(dispatcher: MessageDispatcher, commanders: Vec<Arc<dyn AsyncIrcCommander>>) -> Self {
+        let mut watched_files = HashMap::new();
+        for path in commanders.iter().flat_map(|c| c.watched_files()) {
+            if let Some(modified) = modified_time(&path) {
+                watched_files.insert(path, modified);
+            }
+        }
+        Self {
+            commanders,
+            dispatcher,
+            diagnostics_channel: None,
+            unauthorized_policy: UnauthorizedPolicy::Ignore,
+            handler_timeout: DEFAULT_HANDLER_TIMEOUT,
+            cooldowns: HashMap::new(),
+            cooldown_tracker: Arc::new(Mutex::new(CooldownTracker::new())),
+            triggers: None,
+            prefixes: HashMap::new(),
+            default_prefixes: vec![DEFAULT_PREFIX.to_string()],
+            enabled_commanders: HashMap::new(),
+            bot_login: None,
+            watched_files,
+            registry: CommandRegistry::new(),
+        }
+    }
+
+    /// Also post reload diagnostics to the given channel, eg a mod-only channel.
+    pub fn with_diagnostics_channel(mut self, channel: String) -> Self {
+        self.diagnostics_channel = Some(channel);
+        self
+    }
+
+    pub fn with_unauthorized_policy(mut self, policy: UnauthorizedPolicy) -> Self {
+        self.unauthorized_policy = policy;
+        self
+    }
+
+    /// Override the cooldowns of the given command.
+    pub fn with_cooldown(mut self, cmd: &str, cooldown: Cooldown) -> Self {
+        self.cooldowns.insert(cmd.to_string(), cooldown);
+        self
+    }
+
+    /// Discard the response of any command handler that runs longer than `timeout`.
+    pub fn with_handler_timeout(mut self, timeout: Duration) -> Self {
+        self.handler_timeout = timeout;
+        self
+    }
+
+    /// Respond to messages matching `triggers`, see `Triggers`. The triggers file is watched
+    /// for modifications like the commanders' files.
+    pub fn with_triggers(mut self, triggers: Triggers) -> Self {
+        let path = triggers.path().to_path_buf();
+        if let Some(modified) = modified_time(&path) {
+            self.watched_files.insert(path, modified);
+        }
+        self.triggers = Some(triggers);
+        self
+    }
+
+    /// Prefixes commands are recognised by in channels without their own, `!` by default.
+    pub fn with_default_prefixes(mut self, prefixes: Vec<String>) -> Self {
+        self.default_prefixes = prefixes;
+        self
+    }
+
+    /// Prefixes commands are recognised by in `channel`, eg `!` and `?`.
+    pub fn with_prefixes(mut self, channel: &str, prefixes: Vec<String>) -> Self {
+        self.prefixes.insert(channel.to_lowercase(), prefixes);
+        self
+    }
+
+    /// Only consult the commanders named `names` in `channel`, see `AsyncIrcCommander::name`.
+    pub fn with_commanders(mut self, channel: &str, names: Vec<String>) -> Self {
+        self.enabled_commanders
+            .insert(channel.to_lowercase(), names);
+        self
+    }
+
+    /// Keep `registry` up to date with the commands served by the commanders and the composer
+    /// itself, see `CommandRegistry`.
+    pub fn with_registry(mut self, registry: CommandRegistry) -> Self {
+        registry.update(
+            "composer",
+            BUILTIN_COMMANDS.iter().map(|s| s.to_string()).collect(),
+        );
+        for commander in self.commanders.iter() {
+            registry.update(commander.name(), commander.get_commands());
+        }
+        self.registry = registry;
+        self
+    }
+
+    /// Also recognise commands addressed to the bot by name, eg "@botname help".
+    pub fn with_bot_login(mut self, login: &str) -> Self {
+        self.bot_login = Some(login.to_lowercase());
+        self
+    }
+
+    fn prefixes(&self, channel: &str) -> &[String] {
+        match self.prefixes.get(channel) {
+            Some(prefixes) => prefixes,
+            None => &self.default_prefixes,
+        }
+    }
+
+    /// Commanders enabled in `channel`, in order of precedence.
+    fn commanders<'a>(
+        &'a self,
+        channel: &'a str,
+    ) -> impl Iterator<Item = &'a Arc<dyn AsyncIrcCommander>> + 'a {
+        let enabled = self.enabled_commanders.get(channel);
+        self.commanders
+            .iter()
+            .filter(move |commander| match enabled {
+                Some(names) => names.iter().any(|name| name == commander.name()),
+                None => true,
+            })
+    }
+
+    /// Split a message invoking a command into the prefix it was invoked with, the command and
+    /// its arguments.
+    fn parse_command<'a>(&'a self, msg: &'a PrivmsgMessage) -> Option<(&'a str, &'a str, &'a str)> {
+        let text = msg.message_text.as_str();
+        let prefixes = self.prefixes(&msg.channel_login);
+
+        // @<botname> [prefix]<botcmd> <arguments>
+        let addressed = self
+            .bot_login
+            .as_deref()
+            .and_then(|login| strip_mention(text, login));
+        let (prefix, rest) = match addressed {
+            Some(rest) => match longest_prefix(rest, prefixes) {
+                Some(prefix) => (prefix, &rest[prefix.len()..]),
+                None => (
+                    prefixes.first().map_or(DEFAULT_PREFIX, |p| p.as_str()),
+                    rest,
+                ),
+            },
+            // <prefix><botcmd> <arguments>
+            None => {
+                let prefix = longest_prefix(text, prefixes)?;
+                (prefix, &text[prefix.len()..])
+            }
+        };
+
+        // underscores don't break words, so `!my_command` is found whole
+        let command = rest.split_word_bounds().next()?;
+        if !is_command_name(command) {
+            return None;
+        }
+        Some((prefix, command, &rest[command.len()..]))
+    }
+
+    pub async fn run_commanders(&mut self) {
+        let mut reload_interval = tokio::time::interval(RELOAD_CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                message = self.dispatcher.recv() => match message {
+                    Some(IrcEvent::Message(ServerMessage::Privmsg(msg))) => self.handle_privmsg(msg),
+                    Some(_) => continue,
+                    None => break,
+                },
+                _ = reload_interval.tick() => self.reload_changed(),
+            }
+        }
+    }
+
+    fn handle_privmsg(&mut self, msg: PrivmsgMessage) {
+        let (prefix, command, args) = match self.parse_command(&msg) {
+            Some(parsed) => parsed,
+            None => {
+                self.handle_triggers(&msg);
+                return;
+            }
+        };
+        let ctx = CommandContext::new(&msg, prefix, command, args);
+        let prefix = ctx.prefix.as_str();
+        let command = ctx.command.as_str();
+        let role = ctx.role;
+
+        match command {
+            "help" | "commands" => match ctx.args.split_whitespace().next() {
+                Some(cmd) => self.send_help(&ctx, &cmd.trim_start_matches(prefix).to_lowercase()),
+                None => self.send_command_list(&ctx),
+            },
+            _ => (),
+        }
+
+        // commanders the caller may invoke `command` on, in order of precedence, along with the
+        // name and cooldowns of the command they'd run
+        let mut candidates = Vec::new();
+        // cooldowns started for this invocation, released again unless their command runs
+        let mut reservations: Vec<Reservation> = Vec::new();
+        for commander in self.commanders(&ctx.channel_login) {
+            let required_role = commander.required_role(command);
+            if role < required_role {
+                if commander.get_commands().iter().any(|cmd| cmd == command) {
+                    println!(
+                        "[{}] {} lacks the role to invoke {}{} ({} only)",
+                        ctx.channel_login, ctx.sender.login, prefix, command, required_role
+                    );
+                    if self.unauthorized_policy == UnauthorizedPolicy::Refuse {
+                        self.send_msg(
+                            &ctx.channel_login,
+                            &format!(
+                                "@{} {}{} is restricted to {}",
+                                ctx.sender.login, prefix, command, required_role
+                            ),
+                        );
+                    }
+                    break;
+                }
+                continue;
+            }
+
+            // aliases share the cooldowns of the command they stand for
+            let name = commander.command_name(command);
+            let cooldown = match self.cooldowns.get(&name) {
+                Some(cooldown) => *cooldown,
+                None => commander.cooldown(command),
+            };
+            let exempt = cooldown.moderators_exempt && role >= Role::Moderator;
+            let reserved = reservations
+                .iter()
+                .any(|reservation| reservation.command() == name);
+            if !cooldown.is_disabled() && !exempt && !reserved {
+                let started = lock_tracker(&self.cooldown_tracker).try_start(
+                    &ctx.channel_login,
+                    &name,
+                    &ctx.sender.login,
+                    &cooldown,
+                );
+                match started {
+                    Ok(reservation) => reservations.push(reservation),
+                    Err(remaining) => {
+                        println!(
+                            "[{}] {}{} from {} ignored, on cooldown for {}s",
+                            ctx.channel_login,
+                            prefix,
+                            command,
+                            ctx.sender.login,
+                            remaining.as_secs()
+                        );
+                        break;
+                    }
+                }
+            }
+
+            if let Some(signature) = commander.signature(command) {
+                if let Err(e) = ctx.parse_args(&signature) {
+                    println!(
+                        "[{}] {}{} from {} ignored: {}",
+                        ctx.channel_login, prefix, command, ctx.sender.login, e
+                    );
+                    send_component_message(
+                        &self.dispatcher.command_sender(),
+                        ComponentMessage::Reply(ReplyMessage {
+                            channel: ctx.channel_login.clone(),
+                            reply_to_id: ctx.message_id.clone(),
+                            message: e.to_string(),
+                        }),
+                    );
+                    break;
+                }
+            }
+
+            candidates.push((commander.clone(), name));
+        }
+        if candidates.is_empty() {
+            release_cooldowns(&self.cooldown_tracker, &reservations, None);
+            return;
+        }
+
+        // run the handlers in the background so a slow command doesn't hold up the others
+        let sender = self.dispatcher.command_sender();
+        let cooldown_tracker = self.cooldown_tracker.clone();
+        let registry = self.registry.clone();
+        let timeout = self.handler_timeout;
+        tokio::spawn(async move {
+            // name of the command that ran, the only one whose cooldowns are kept
+            let mut ran = None;
+            for (commander, name) in candidates.iter() {
+                let handled = tokio::time::timeout(timeout, commander.handle_msg(&ctx)).await;
+                match handled {
+                    Ok(Some(responses)) => {
+                        ran = Some(name.as_str());
+                        // the command may have added or removed commands, eg !addcom
+                        registry.update(commander.name(), commander.get_commands());
+                        for response in responses.into_iter() {
+                            if let Some(message) = response.into_component_message(&ctx) {
+                                if let Err(e) = sender.send(message).await {
+                                    println!("failed to send message to channel: {}", e);
+                                }
+                            }
+                        }
+                        break;
+                    }
+                    Ok(None) => continue,
+                    Err(_) => {
+                        println!(
+                            "[{}] !{} from {} timed out after {}s",
+                            ctx.channel_login,
+                            ctx.command,
+                            ctx.sender.login,
+                            timeout.as_secs_f32()
+                        );
+                        ran = Some(name.as_str());
+                        break;
+                    }
+                }
+            }
+            release_cooldowns(&cooldown_tracker, &reservations, ran);
+        });
+    }
+
+    /// Send the usage and description of `cmd`.
+    fn send_help(&self, ctx: &CommandContext, cmd: &str) {
+        let commander = self.commanders(&ctx.channel_login).find(|commander| {
+            commander.required_role(cmd) <= ctx.role
+                && (commander.get_commands().iter().any(|c| c == cmd)
+                    || commander.description(cmd).is_some())
+        });
+        let help = match commander {
+            Some(commander) => {
+                let usage = commander.usage(cmd, &ctx.prefix);
+                match commander.description(cmd) {
+                    Some(description) => format!("{} - {}", usage, description),
+                    None => usage,
+                }
+            }
+            None => format!(
+                "@{} {}{} isn't a command",
+                ctx.sender.login, ctx.prefix, cmd
+            ),
+        };
+        self.send_msg(&ctx.channel_login, &help);
+    }
+
+    /// Send the names of the commands the caller is allowed to invoke, over as many messages as
+    /// needed.
+    fn send_command_list(&self, ctx: &CommandContext) {
+        let mut commands: Vec<String> = self
+            .commanders(&ctx.channel_login)
+            .flat_map(|commander| {
+                commander
+                    .get_commands()
+                    .into_iter()
+                    .filter(|cmd| commander.required_role(cmd) <= ctx.role)
+                    .collect::<Vec<String>>()
+            })
+            .collect();
+        commands.sort();
+        commands.dedup();
+        for message in join_within_limit(&commands, ", ", MAX_MESSAGE_LENGTH) {
+            self.send_msg(&ctx.channel_login, &message);
+        }
+    }
+
+    fn handle_triggers(&mut self, msg: &PrivmsgMessage) {
+        let triggers = match self.triggers.as_mut() {
+            Some(triggers) => triggers,
+            None => return,
+        };
+        let mut ctx = CommandContext::new(msg, "", "", &msg.message_text);
+        // the first matching trigger the chatter may fire and that isn't cooling down
+        let fired = triggers.matching(&msg.message_text).find(|trigger| {
+            if ctx.role < trigger.role() {
+                return false;
+            }
+            // triggers share the cooldown tracker with commands, so key them apart from commands
+            let key = format!("trigger:{}", trigger.name);
+            let cooldown = trigger.cooldown();
+            let exempt = cooldown.moderators_exempt && ctx.role >= Role::Moderator;
+            cooldown.is_disabled()
+                || exempt
+                || lock_tracker(&self.cooldown_tracker)
+                    .try_start(&ctx.channel_login, &key, &ctx.sender.login, &cooldown)
+                    .is_ok()
+        });
+        match fired {
+            Some(trigger) => ctx.command = trigger.name.clone(),
+            None => return,
+        }
+
+        println!(
+            "[{}] trigger {} fired by {}",
+            ctx.channel_login, ctx.command, ctx.sender.login
+        );
+        for response in triggers.fire(&ctx.command, &ctx).into_iter() {
+            if let Some(message) = response.into_component_message(&ctx) {
+                send_component_message(&self.dispatcher.command_sender(), message);
+            }
+        }
+    }
+
+    /// Reload every commander whose watched files were modified since the last check. A commander
+    /// that fails to reload keeps its previous command set.
+    fn reload_changed(&mut self) {
+        let mut diagnostics = Vec::new();
+        for commander in self.commanders.iter() {
+            let mut changed = Vec::new();
+            for path in commander.watched_files() {
+                let modified = match modified_time(&path) {
+                    Some(modified) => modified,
+                    // the file may be mid-write or temporarily replaced by an editor; keep the
+                    // last seen modification time and try again on the next check
+                    None => continue,
+                };
+                if self.watched_files.get(&path) != Some(&modified) {
+                    self.watched_files.insert(path.clone(), modified);
+                    changed.push(path);
+                }
+            }
+            if changed.is_empty() {
+                continue;
+            }
+
+            let paths: Vec<String> = changed.iter().map(|p| p.display().to_string()).collect();
+            match commander.reload() {
+                Ok(()) => {
+                    println!("reloaded commands from {}", paths.join(", "));
+                    self.registry
+                        .update(commander.name(), commander.get_commands());
+                }
+                Err(e) => {
+                    let diagnostic = format!(
+                        "failed to reload commands from {}, keeping previous commands: {}",
+                        paths.join(", "),
+                        e
+                    );
+                    println!("{}", diagnostic);
+                    diagnostics.push(diagnostic);
+                }
+            }
+        }
+
+        if let Some(triggers) = self.triggers.as_mut() {
+            let path = triggers.path().to_path_buf();
+            if let Some(modified) = modified_time(&path) {
+                if self.watched_files.get(&path) != Some(&modified) {
+                    self.watched_files.insert(path.clone(), modified);
+                    match triggers.reload() {
+                        Ok(()) => println!("reloaded triggers from {}", path.display()),
+                        Err(e) => {
+                            let diagnostic = format!(
+                                "failed to reload triggers from {}, keeping previous triggers: {}",
+                                path.display(),
+                                e
+                            );
+                            println!("{}", diagnostic);
+                            diagnostics.push(diagnostic);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(channel) = &self.diagnostics_channel {
+            for diagnostic in diagnostics.iter() {
+                self.send_msg(channel, diagnostic);
+            }
+        }
+    }
+
+    pub fn send_msg(&self, channel: &str, message: &str) {
+        send_component_message(
+            &self.dispatcher.command_sender(),
+            ComponentMessage::Chat(ChatMessage {
+                channel: channel.to_string(),
+                message: message.to_string(),
+            }),
+        )
+    }
+}
+
+/// Join `items` with `separator` into as few messages of at most `limit` characters as possible.
+/// Items longer than `limit` are sent in a message of their own.
+fn join_within_limit(items: &[String], separator: &str, limit: usize) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut message = String::new();
+    for item in items.iter() {
+        if !message.is_empty()
+            && message.chars().count() + separator.chars().count() + item.chars().count() > limit
+        {
+            messages.push(std::mem::take(&mut message));
+        }
+        if !message.is_empty() {
+            message.push_str(separator);
+        }
+        message.push_str(item);
+    }
+    if !message.is_empty() {
+        messages.push(message);
+    }
+    messages
+}
+
+/// The remainder of `text` if it starts by mentioning `login`, eg "@botname, help".
+fn strip_mention<'a>(text: &'a str, login: &str) -> Option<&'a str> {
+    let mention = text.strip_prefix('@')?;
+    let name = mention.get(..login.len())?;
+    if !name.eq_ignore_ascii_case(login) {
+        return None;
+    }
+    let rest = &mention[login.len()..];
+    if !rest.starts_with(|c: char| c.is_whitespace() || c == ',' || c == ':') {
+        return None;
+    }
+    Some(rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',' || c == ':'))
+}
+
+/// The longest of `prefixes` that `text` starts with, so eg `!!` wins over `!`.
+fn longest_prefix<'a>(text: &str, prefixes: &'a [String]) -> Option<&'a str> {
+    prefixes
+        .iter()
+        .filter(|prefix| !prefix.is_empty() && text.starts_with(prefix.as_str()))
+        .max_by_key(|prefix| prefix.len())
+        .map(|prefix| prefix.as_str())
+}
+
+// for handlers that can't wait, the message is dropped if the queue is full
+fn send_component_message(sender: &CommandSender, message: ComponentMessage) {
+    if let Err(e) = sender.try_send(message) {
+        println!("failed to send message to channel: {}", e);
+    }
+}
+
+/// Release the cooldowns in `reservations` that weren't started for the command `ran`, eg
+/// because no commander handled the command after all.
+fn release_cooldowns(
+    tracker: &Mutex<CooldownTracker>,
+    reservations: &[Reservation],
+    ran: Option<&str>,
+) {
+    let mut tracker = lock_tracker(tracker);
+    for reservation in reservations
+        .iter()
+        .filter(|reservation| Some(reservation.command()) != ran)
+    {
+        tracker.release(reservation);
+    }
+}
+
+fn lock_tracker(tracker: &Mutex<CooldownTracker>) -> std::sync::MutexGuard<'_, CooldownTracker> {
+    tracker.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irc::{self, EventBus};
+
+    fn privmsg(text: &str) -> PrivmsgMessage {
+        irc::privmsg("viewer", "uuayn", text)
+    }
+
+    #[test]
+    fn parse_command_names() {
+        let (bus, _commands) = EventBus::new();
+        let composer = CommanderComposer::new(bus.subscriber("test"), Vec::new());
+        let parse = |text: &str| {
+            composer
+                .parse_command(&privmsg(text))
+                .map(|(prefix, command, args)| {
+                    (prefix.to_string(), command.to_string(), args.to_string())
+                })
+        };
+        assert_eq!(
+            parse("!meow"),
+            Some(("!".to_string(), "meow".to_string(), "".to_string()))
+        );
+        assert_eq!(
+            parse("!my_command some args"),
+            Some((
+                "!".to_string(),
+                "my_command".to_string(),
+                " some args".to_string()
+            ))
+        );
+        assert_eq!(parse("!?"), None);
+        assert_eq!(parse("meow"), None);
+    }
+
+    #[test]
+    fn strip_mentions_of_the_bot() {
+        assert_eq!(strip_mention("@bot !cmd", "bot"), Some("!cmd"));
+        assert_eq!(strip_mention("@bot: !cmd", "bot"), Some("!cmd"));
+        assert_eq!(strip_mention("@bot, help", "bot"), Some("help"));
+        assert_eq!(strip_mention("@BoT !cmd", "bot"), Some("!cmd"));
+        assert_eq!(strip_mention("bot: !cmd", "bot"), None);
+        assert_eq!(strip_mention("@botty !cmd", "bot"), None);
+        assert_eq!(strip_mention("@bot", "bot"), None);
+        assert_eq!(strip_mention("@someone !cmd", "bot"), None);
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let prefixes = vec!["!".to_string(), "!!".to_string(), "".to_string()];
+        assert_eq!(longest_prefix("!!cmd", &prefixes), Some("!!"));
+        assert_eq!(longest_prefix("!cmd", &prefixes), Some("!"));
+        assert_eq!(longest_prefix("cmd", &prefixes), None);
+
+        let prefixes = vec!["bot,".to_string()];
+        assert_eq!(longest_prefix("bot, cmd", &prefixes), Some("bot,"));
+        assert_eq!(longest_prefix("BOT, cmd", &prefixes), None);
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use twitch_irc::message::{Badge, PrivmsgMessage, TwitchUserBasics};
+use unicode_segmentation::UWordBounds;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::commander::args::{Args, Signature, UsageError};
+use crate::commander::Role;
+
+/// Everything a commander may want to know about the message that invoked one of its commands.
+#[derive(Clone, Debug)]
+pub struct CommandContext {
+    /// The prefix the command was invoked with, eg `!`.
+    pub prefix: String,
+    /// The command word, without prefix.
+    pub command: String,
+    /// The remainder of the message following the command word.
+    pub args: String,
+
+    pub channel_login: String,
+    pub channel_id: String,
+    pub sender: TwitchUserBasics,
+    pub badges: Vec<Badge>,
+    pub role: Role,
+    pub message_id: String,
+    pub message_text: String,
+    pub server_timestamp: DateTime<Utc>,
+}
+
+impl CommandContext {
+    pub fn new(msg: &PrivmsgMessage, prefix: &str, command: &str, args: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            command: command.to_string(),
+            args: args.to_string(),
+            channel_login: msg.channel_login.clone(),
+            channel_id: msg.channel_id.clone(),
+            sender: msg.sender.clone(),
+            badges: msg.badges.clone(),
+            role: Role::of_sender(msg),
+            message_id: msg.message_id.clone(),
+            message_text: msg.message_text.clone(),
+            server_timestamp: msg.server_timestamp,
+        }
+    }
+
+    /// Parse the command arguments according to `signature`.
+    pub fn parse_args(&self, signature: &Signature) -> Result<Args, UsageError> {
+        signature.parse(&self.prefix, &self.args)
+    }
+
+    /// Word bounds of the command arguments, including whitespace and punctuation.
+    pub fn words(&self) -> UWordBounds<'_> {
+        self.args.split_word_bounds()
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Cooldowns for a single command, in seconds. A value of zero disables that cooldown. In YAML:
+///
+/// ```yaml
+/// cooldown:
+///   global: 30
+///   per_user: 120
+///   moderators_exempt: true
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Cooldown {
+    /// Minimum time between any two invocations of the command.
+    #[serde(default)]
+    pub global: u64,
+    /// Minimum time between two invocations of the command by the same chatter.
+    #[serde(default, alias = "user")]
+    pub per_user: u64,
+    /// Whether moderators (and the broadcaster) may ignore the cooldowns.
+    #[serde(default)]
+    pub moderators_exempt: bool,
+}
+
+impl Cooldown {
+    pub fn new(global: Duration, per_user: Duration) -> Self {
+        Self {
+            global: global.as_secs(),
+            per_user: per_user.as_secs(),
+            moderators_exempt: false,
+        }
+    }
+
+    pub fn moderators_exempt(mut self) -> Self {
+        self.moderators_exempt = true;
+        self
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.global == 0 && self.per_user == 0
+    }
+}
+
+/// Cooldowns started by `CooldownTracker::try_start`.
+#[derive(Clone, Debug)]
+pub struct Reservation {
+    channel: String,
+    cmd: String,
+    user: String,
+    // ends of the cooldowns started, if any
+    global: Option<Instant>,
+    per_user: Option<Instant>,
+}
+
+impl Reservation {
+    /// Name of the command whose cooldowns were started.
+    pub fn command(&self) -> &str {
+        &self.cmd
+    }
+}
+
+/// Tracks when the cooldowns of each command run out, separately in each channel. Commands are
+/// tracked by name, see `IrcCommander::command_name`, so aliases share their cooldowns.
+#[derive(Default)]
+pub struct CooldownTracker {
+    // (channel, command) -> end of the global cooldown
+    global: HashMap<(String, String), Instant>,
+    // (channel, command, chatter) -> end of the chatter's cooldown
+    per_user: HashMap<(String, String, String), Instant>,
+}
+
+impl CooldownTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time left before `user` may invoke `cmd` in `channel` again, if any.
+    pub fn remaining(&self, channel: &str, cmd: &str, user: &str) -> Option<Duration> {
+        let now = Instant::now();
+        let global = self
+            .global
+            .get(&(channel.to_string(), cmd.to_string()))
+            .and_then(|end| end.checked_duration_since(now));
+        let per_user = self
+            .per_user
+            .get(&(channel.to_string(), cmd.to_string(), user.to_string()))
+            .and_then(|end| end.checked_duration_since(now));
+        global
+            .max(per_user)
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Start the cooldowns of `cmd` in `channel` for `user`, unless they're still running, in
+    /// which case the time left is returned. Checking and starting them at once means repeats
+    /// arriving while the command is still being handled are held off too.
+    pub fn try_start(
+        &mut self,
+        channel: &str,
+        cmd: &str,
+        user: &str,
+        cooldown: &Cooldown,
+    ) -> std::result::Result<Reservation, Duration> {
+        if let Some(remaining) = self.remaining(channel, cmd, user) {
+            return Err(remaining);
+        }
+
+        let now = Instant::now();
+        self.prune(now);
+        let mut reservation = Reservation {
+            channel: channel.to_string(),
+            cmd: cmd.to_string(),
+            user: user.to_string(),
+            global: None,
+            per_user: None,
+        };
+        if cooldown.global > 0 {
+            let end = now + Duration::from_secs(cooldown.global);
+            self.global
+                .insert((channel.to_string(), cmd.to_string()), end);
+            reservation.global = Some(end);
+        }
+        if cooldown.per_user > 0 {
+            let end = now + Duration::from_secs(cooldown.per_user);
+            self.per_user.insert(
+                (channel.to_string(), cmd.to_string(), user.to_string()),
+                end,
+            );
+            reservation.per_user = Some(end);
+        }
+        Ok(reservation)
+    }
+
+    /// Undo the cooldowns started by `reservation`, eg because the command was rejected after
+    /// all, unless they've been started again since.
+    pub fn release(&mut self, reservation: &Reservation) {
+        let global_key = (reservation.channel.clone(), reservation.cmd.clone());
+        if reservation.global.is_some()
+            && self.global.get(&global_key) == reservation.global.as_ref()
+        {
+            self.global.remove(&global_key);
+        }
+        let per_user_key = (
+            reservation.channel.clone(),
+            reservation.cmd.clone(),
+            reservation.user.clone(),
+        );
+        if reservation.per_user.is_some()
+            && self.per_user.get(&per_user_key) == reservation.per_user.as_ref()
+        {
+            self.per_user.remove(&per_user_key);
+        }
+    }
+
+    /// Forget cooldowns that have run out, so chatters who invoked a command once aren't
+    /// remembered forever.
+    fn prune(&mut self, now: Instant) {
+        self.global.retain(|_, end| *end > now);
+        self.per_user.retain(|_, end| *end > now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cooldown(global: u64, per_user: u64) -> Cooldown {
+        Cooldown {
+            global,
+            per_user,
+            moderators_exempt: false,
+        }
+    }
+
+    #[test]
+    fn try_start_holds_off_repeats() {
+        let mut tracker = CooldownTracker::new();
+        assert!(tracker
+            .try_start("chan", "meow", "viewer", &cooldown(30, 0))
+            .is_ok());
+        assert!(tracker
+            .try_start("chan", "meow", "other", &cooldown(30, 0))
+            .is_err());
+        // per channel
+        assert!(tracker
+            .try_start("elsewhere", "meow", "viewer", &cooldown(30, 0))
+            .is_ok());
+    }
+
+    #[test]
+    fn per_user_cooldowns_are_per_user() {
+        let mut tracker = CooldownTracker::new();
+        assert!(tracker
+            .try_start("chan", "meow", "viewer", &cooldown(0, 30))
+            .is_ok());
+        assert!(tracker
+            .try_start("chan", "meow", "viewer", &cooldown(0, 30))
+            .is_err());
+        assert!(tracker
+            .try_start("chan", "meow", "other", &cooldown(0, 30))
+            .is_ok());
+    }
+
+    #[test]
+    fn release_rolls_back() {
+        let mut tracker = CooldownTracker::new();
+        let reservation = tracker
+            .try_start("chan", "meow", "viewer", &cooldown(30, 30))
+            .unwrap();
+        tracker.release(&reservation);
+        assert_eq!(tracker.remaining("chan", "meow", "viewer"), None);
+        assert!(tracker
+            .try_start("chan", "meow", "viewer", &cooldown(30, 30))
+            .is_ok());
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use tokio::sync::watch;
+
+use crate::commander::args::{is_command_name, ArgKind, Signature};
+use crate::commander::files::write_atomically;
+use crate::commander::{CommandContext, CommandRegistry, CommandResponse, IrcCommander, Role};
+use crate::error::Result;
+
+pub type Counters = BTreeMap<String, i64>;
+
+/// Named counters, eg `!deaths`. Anyone can query a counter, moderators can change it:
+///
+/// * `!deaths` - show the count
+/// * `!deaths ++`, `!deaths +5` - increment
+/// * `!deaths --`, `!deaths -5` - decrement
+/// * `!deaths set 10`, `!deaths reset` - set the count
+/// * `!counter add deaths`, `!counter remove deaths` - add or remove a counter
+///
+/// Counters are saved to `counters_file` after every change and published to subscribers, eg the
+/// overlay. Like custom commands, counters can't take the names of commands served by other
+/// commanders, see `with_registry`.
+pub struct CounterCommander {
+    counters_file: PathBuf,
+    counters: Counters,
+    sender: watch::Sender<Counters>,
+    registry: CommandRegistry,
+}
+
+enum CounterChange {
+    Add(i64),
+    Set(i64),
+}
+
+impl CounterCommander {
+    /// Load counters from `counters_file`, starting with none if it doesn't exist yet.
+    pub fn new(counters_file: PathBuf) -> Result<Self> {
+        let counters = if counters_file.exists() {
+            load_counters(&counters_file)?
+        } else {
+            Counters::new()
+        };
+        let (sender, _) = watch::channel(counters.clone());
+        Ok(Self {
+            counters_file,
+            counters,
+            sender,
+            registry: CommandRegistry::new(),
+        })
+    }
+
+    /// Refuse to add counters named like commands served by the other commanders in `registry`.
+    pub fn with_registry(mut self, registry: CommandRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Receive the current counter values whenever they change.
+    pub fn subscribe(&self) -> watch::Receiver<Counters> {
+        self.sender.subscribe()
+    }
+
+    fn manage(&mut self, ctx: &CommandContext) -> CommandResponse {
+        let signature = manage_signature();
+        let args = match ctx.parse_args(&signature) {
+            Ok(args) => args,
+            Err(e) => return CommandResponse::Reply(e.to_string()),
+        };
+        let action = args.text("action").unwrap_or_default();
+        let prefix = ctx.prefix.as_str();
+        let name = args
+            .text("name")
+            .unwrap_or_default()
+            .trim_start_matches(prefix)
+            .to_lowercase();
+        if !is_command_name(&name) {
+            return CommandResponse::Reply(format!("usage: {}", signature.usage(prefix)));
+        }
+        let reply = match action {
+            "add" if self.counters.contains_key(&name) => {
+                return CommandResponse::Reply(format!("{}{} already exists", prefix, name))
+            }
+            "add" if name == "counter" || self.registry.served_by(&name, self.name()).is_some() => {
+                return CommandResponse::Reply(format!("{}{} is already a command", prefix, name))
+            }
+            "add" => {
+                self.counters.insert(name.clone(), 0);
+                format!("added counter {}{}", prefix, name)
+            }
+            "remove" => match self.counters.remove(&name) {
+                Some(_) => format!("removed counter {}{}", prefix, name),
+                None => return CommandResponse::Reply(format!("{}{} doesn't exist", prefix, name)),
+            },
+            _ => return CommandResponse::Reply(format!("usage: {}", signature.usage(prefix))),
+        };
+        self.changed();
+        CommandResponse::Reply(reply)
+    }
+
+    fn update(&mut self, ctx: &CommandContext) -> CommandResponse {
+        let change = match parse_change(&ctx.args) {
+            Some(change) => change,
+            // anything that isn't a change is a query
+            None => return self.show(&ctx.command),
+        };
+        if ctx.role < Role::Moderator {
+            return self.show(&ctx.command);
+        }
+        if let Some(count) = self.counters.get_mut(&ctx.command) {
+            *count = match change {
+                CounterChange::Add(n) => count.saturating_add(n),
+                CounterChange::Set(n) => n,
+            };
+        }
+        self.changed();
+        self.show(&ctx.command)
+    }
+
+    fn show(&self, name: &str) -> CommandResponse {
+        let count = self.counters.get(name).copied().unwrap_or_default();
+        CommandResponse::Say(format!("{}: {}", name, count))
+    }
+
+    fn changed(&mut self) {
+        self.sender.send_replace(self.counters.clone());
+        if let Err(e) = save_counters(&self.counters_file, &self.counters) {
+            println!(
+                "failed to save counters to {}: {}",
+                self.counters_file.display(),
+                e
+            );
+        }
+    }
+}
+
+impl IrcCommander for CounterCommander {
+    fn name(&self) -> &'static str {
+        "counters"
+    }
+
+    fn watched_files(&self) -> Vec<PathBuf> {
+        vec![self.counters_file.clone()]
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        let counters = load_counters(&self.counters_file)?;
+        if counters != self.counters {
+            self.counters = counters;
+            self.sender.send_replace(self.counters.clone());
+        }
+        Ok(())
+    }
+
+    fn signature(&self, cmd: &str) -> Option<Signature> {
+        match cmd {
+            "counter" => Some(manage_signature()),
+            _ => None,
+        }
+    }
+
+    fn description(&self, cmd: &str) -> Option<String> {
+        match cmd {
+            "counter" => Some("add or remove a counter".to_string()),
+            cmd if self.counters.contains_key(cmd) => Some(format!(
+                "show the {} counter, moderators can change it with ++, --, +N, -N, set N or reset",
+                cmd
+            )),
+            _ => None,
+        }
+    }
+
+    fn get_commands(&self) -> Vec<String> {
+        let mut commands = vec!["counter".to_string()];
+        commands.extend(self.counters.keys().cloned());
+        commands
+    }
+
+    fn required_role(&self, cmd: &str) -> Role {
+        match cmd {
+            "counter" => Role::Moderator,
+            _ => Role::Everyone,
+        }
+    }
+
+    fn handle_msg(&mut self, ctx: &CommandContext) -> Option<Vec<CommandResponse>> {
+        if ctx.command == "counter" {
+            return Some(vec![self.manage(ctx)]);
+        }
+        if !self.counters.contains_key(&ctx.command) {
+            return None;
+        }
+        Some(vec![self.update(ctx)])
+    }
+}
+
+fn manage_signature() -> Signature {
+    Signature::new("counter")
+        .required("action", ArgKind::Word)
+        .required("name", ArgKind::Word)
+}
+
+fn parse_change(args: &str) -> Option<CounterChange> {
+    let args = args.trim();
+    let change = match args {
+        "++" | "+" => CounterChange::Add(1),
+        "--" | "-" => CounterChange::Add(-1),
+        "reset" => CounterChange::Set(0),
+        _ => {
+            if let Some(n) = args.strip_prefix("set") {
+                CounterChange::Set(n.trim().parse().ok()?)
+            } else if let Some(n) = args.strip_prefix('+') {
+                CounterChange::Add(n.trim().parse().ok()?)
+            } else if let Some(n) = args.strip_prefix('-') {
+                CounterChange::Add(-n.trim().parse::<i64>().ok()?)
+            } else {
+                return None;
+            }
+        }
+    };
+    Some(change)
+}
+
+fn load_counters(path: &Path) -> Result<Counters> {
+    let mut file = File::open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(serde_yaml::from_str(&contents)?)
+}
+
+fn save_counters(path: &Path, counters: &Counters) -> Result<()> {
+    write_atomically(path, &serde_yaml::to_string(counters)?)
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::commander::args::{is_command_name, ArgKind, Signature};
+use crate::commander::template::TemplateVars;
+use crate::commander::{
+    CommandContext, CommandRegistry, CommandResponse, Cooldown, IrcCommander, Responses, Role,
+    StaticCommand, StaticCommands,
+};
+use crate::error::Result;
+
+const MANAGEMENT_COMMANDS: [&str; 3] = ["addcom", "editcom", "delcom"];
+
+/// Simple text commands managed by moderators from chat:
+///
+/// * `!addcom <name> <response>` - add a command, the response may use template variables
+/// * `!editcom <name> <response>` - replace the response of an existing command
+/// * `!delcom <name>` - delete a command
+///
+/// Commands are saved to `commands_file` after every change, in the same format as the
+/// `HardCodedCommander` static commands file. Names served by other commanders, see
+/// `with_registry`, can't be added.
+pub struct CustomCommander {
+    commands_file: PathBuf,
+    commands: StaticCommands,
+    counts: HashMap<String, u64>,
+    started: Instant,
+    registry: CommandRegistry,
+}
+
+impl CustomCommander {
+    /// Load custom commands from `commands_file`, starting with none if it doesn't exist yet.
+    pub fn new(commands_file: PathBuf) -> Result<Self> {
+        let commands = if commands_file.exists() {
+            StaticCommands::from_file(&commands_file)?
+        } else {
+            StaticCommands::default()
+        };
+        Ok(Self {
+            commands_file,
+            commands,
+            counts: HashMap::new(),
+            started: Instant::now(),
+            registry: CommandRegistry::new(),
+        })
+    }
+
+    /// Refuse to add commands already served by the other commanders in `registry`.
+    pub fn with_registry(mut self, registry: CommandRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    fn manage(&mut self, ctx: &CommandContext) -> CommandResponse {
+        let signature = management_signature(&ctx.command);
+        let args = match ctx.parse_args(&signature) {
+            Ok(args) => args,
+            Err(e) => return CommandResponse::Reply(e.to_string()),
+        };
+        let prefix = ctx.prefix.as_str();
+        let name = args
+            .text("name")
+            .unwrap_or_default()
+            .trim_start_matches(prefix)
+            .to_lowercase();
+        let response = args.text("response").unwrap_or_default();
+        if !is_command_name(&name) {
+            return CommandResponse::Reply(format!("usage: {}", signature.usage(prefix)));
+        }
+        if MANAGEMENT_COMMANDS.contains(&name.as_str()) {
+            return CommandResponse::Reply(format!("{}{} can't be redefined", prefix, name));
+        }
+
+        let exists = self.commands.get(&name).is_some();
+        let served_elsewhere = self.registry.served_by(&name, self.name()).is_some();
+        let reply = match ctx.command.as_str() {
+            "addcom" if exists => format!(
+                "{0}{1} already exists, use {0}editcom to change it",
+                prefix, name
+            ),
+            "addcom" if served_elsewhere => format!("{}{} is already a command", prefix, name),
+            "editcom" if !exists => format!(
+                "{0}{1} doesn't exist, use {0}addcom to add it",
+                prefix, name
+            ),
+            "addcom" | "editcom" => {
+                let mut command = match self.commands.get(&name) {
+                    Some(command) => command.clone(),
+                    None => StaticCommand::new(String::new()),
+                };
+                command.responses = Responses::One(response.to_string());
+                if let Err(e) = self.commands.insert(name.clone(), command) {
+                    return CommandResponse::Reply(e.to_string());
+                }
+                format!("{}{} saved", prefix, name)
+            }
+            "delcom" => match self.commands.remove(&name) {
+                Some(_) => {
+                    self.counts.remove(&name);
+                    format!("{}{} deleted", prefix, name)
+                }
+                None => format!("{}{} doesn't exist", prefix, name),
+            },
+            _ => unreachable!(),
+        };
+
+        if let Err(e) = self.commands.save(&self.commands_file) {
+            println!(
+                "failed to save custom commands to {}: {}",
+                self.commands_file.display(),
+                e
+            );
+            return CommandResponse::Reply(format!("{}, but saving it failed: {}", reply, e));
+        }
+        CommandResponse::Reply(reply)
+    }
+}
+
+impl IrcCommander for CustomCommander {
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+
+    fn watched_files(&self) -> Vec<PathBuf> {
+        vec![self.commands_file.clone()]
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        self.commands = StaticCommands::from_file(&self.commands_file)?;
+        Ok(())
+    }
+
+    fn required_role(&self, cmd: &str) -> Role {
+        if MANAGEMENT_COMMANDS.contains(&cmd) {
+            return Role::Moderator;
+        }
+        self.commands
+            .get(cmd)
+            .map(|command| command.role)
+            .unwrap_or_default()
+    }
+
+    fn command_name(&self, cmd: &str) -> String {
+        self.commands.canonical_name(cmd).unwrap_or(cmd).to_string()
+    }
+
+    fn cooldown(&self, cmd: &str) -> Cooldown {
+        self.commands
+            .get(cmd)
+            .map(|command| command.cooldown)
+            .unwrap_or_default()
+    }
+
+    fn signature(&self, cmd: &str) -> Option<Signature> {
+        if MANAGEMENT_COMMANDS.contains(&cmd) {
+            return Some(management_signature(cmd));
+        }
+        None
+    }
+
+    fn description(&self, cmd: &str) -> Option<String> {
+        match cmd {
+            "addcom" => Some("add a command, the response may use template variables".to_string()),
+            "editcom" => Some("replace the response of a command".to_string()),
+            "delcom" => Some("delete a command".to_string()),
+            cmd => self.commands.get(cmd)?.description.clone(),
+        }
+    }
+
+    fn usage(&self, cmd: &str, prefix: &str) -> String {
+        if MANAGEMENT_COMMANDS.contains(&cmd) {
+            return management_signature(cmd).usage(prefix);
+        }
+        match self.commands.get(cmd) {
+            Some(command) => command.usage(prefix, cmd),
+            None => format!("{}{}", prefix, cmd),
+        }
+    }
+
+    fn get_commands(&self) -> Vec<String> {
+        let mut commands: Vec<String> = MANAGEMENT_COMMANDS.iter().map(|s| s.to_string()).collect();
+        commands.extend(self.commands.names());
+        commands
+    }
+
+    fn handle_msg(&mut self, ctx: &CommandContext) -> Option<Vec<CommandResponse>> {
+        if MANAGEMENT_COMMANDS.contains(&ctx.command.as_str()) {
+            return Some(vec![self.manage(ctx)]);
+        }
+
+        let name = self.commands.canonical_name(&ctx.command)?.to_string();
+        let command = self.commands.get(&name)?;
+        let count = self.counts.entry(name).or_insert(0);
+        *count += 1;
+        let vars = TemplateVars {
+            count: *count,
+            uptime: self.started.elapsed(),
+        };
+        Some(command.render(ctx, &vars))
+    }
+}
+
+fn management_signature(cmd: &str) -> Signature {
+    let signature = Signature::new(cmd).required("name", ArgKind::Word);
+    match cmd {
+        "delcom" => signature,
+        _ => signature.required("response", ArgKind::Rest),
+    }
+}
@@ -0,0 +1,16 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::error::Result;
+
+/// Write `contents` to `path`, replacing the file only once they have been written out
+/// completely, so a crash or a full disk never leaves a truncated file behind.
+pub(crate) fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
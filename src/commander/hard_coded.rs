@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::commander::args::{normalize_login, tokenize};
+use crate::commander::template::TemplateVars;
+use crate::commander::{
+    CommandContext, CommandResponse, Cooldown, IrcCommander, Role, StaticCommands,
+};
+use crate::error::Result;
+
+pub struct HardCodedCommander {
+    static_commands_file: String,
+    static_commands: StaticCommands,
+    // number of invocations of each static command, by command name
+    counts: HashMap<String, u64>,
+    started: Instant,
+}
+
+impl HardCodedCommander {
+    pub fn new(static_commands_file: String) -> Result<Self> {
+        let static_commands = StaticCommands::from_file(&static_commands_file)?;
+        Ok(Self {
+            static_commands_file,
+            static_commands,
+            counts: HashMap::new(),
+            started: Instant::now(),
+        })
+    }
+
+    pub fn static_commands_file(&self) -> &str {
+        &self.static_commands_file
+    }
+}
+
+impl IrcCommander for HardCodedCommander {
+    fn name(&self) -> &'static str {
+        "hard_coded"
+    }
+
+    fn watched_files(&self) -> Vec<PathBuf> {
+        vec![PathBuf::from(&self.static_commands_file)]
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        self.static_commands = StaticCommands::from_file(&self.static_commands_file)?;
+        Ok(())
+    }
+
+    fn required_role(&self, cmd: &str) -> Role {
+        self.static_commands
+            .get(cmd)
+            .map(|command| command.role)
+            .unwrap_or_default()
+    }
+
+    fn command_name(&self, cmd: &str) -> String {
+        self.static_commands
+            .canonical_name(cmd)
+            .unwrap_or(cmd)
+            .to_string()
+    }
+
+    fn cooldown(&self, cmd: &str) -> Cooldown {
+        self.static_commands
+            .get(cmd)
+            .map(|command| command.cooldown)
+            .unwrap_or_default()
+    }
+
+    fn description(&self, cmd: &str) -> Option<String> {
+        if let Some(command) = self.static_commands.get(cmd) {
+            return command.description.clone();
+        }
+        match cmd {
+            "project" => Some("link to the project being worked on".to_string()),
+            "meow" | "woof" => Some("say hi to the bot".to_string()),
+            "so" | "shoutout" => Some("link to the channels of the given users".to_string()),
+            _ => None,
+        }
+    }
+
+    fn usage(&self, cmd: &str, prefix: &str) -> String {
+        match self.static_commands.get(cmd) {
+            Some(command) => command.usage(prefix, cmd),
+            None if cmd == "so" || cmd == "shoutout" => format!("{}{} <user>...", prefix, cmd),
+            None => format!("{}{}", prefix, cmd),
+        }
+    }
+
+    fn get_commands(&self) -> Vec<String> {
+        let mut commands: Vec<String> = ["meow", "project", "shoutout", "so", "woof"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        for name in self.static_commands.names() {
+            if !commands.contains(&name) {
+                commands.push(name);
+            }
+        }
+        commands
+    }
+
+    fn handle_msg(&mut self, ctx: &CommandContext) -> Option<Vec<CommandResponse>> {
+        // commands in the static commands file replace the built in ones, so their role and
+        // cooldown apply to the response given
+        if let Some(name) = self.static_commands.canonical_name(&ctx.command) {
+            let name = name.to_string();
+            let command = self.static_commands.get(&name)?;
+            let count = self.counts.entry(name).or_insert(0);
+            *count += 1;
+            let vars = TemplateVars {
+                count: *count,
+                uptime: self.started.elapsed(),
+            };
+            return Some(command.render(ctx, &vars));
+        }
+        match ctx.command.as_str() {
+            "project" => Some(vec!["https://github.com/waynr/twitchy-mcbotface".into()]),
+            "meow" => Some(vec!["woof".into()]),
+            "woof" => Some(vec!["meow".into()]),
+            "so" | "shoutout" => Some(shoutout(ctx)),
+            _ => None,
+        }
+    }
+}
+
+fn shoutout(ctx: &CommandContext) -> Vec<CommandResponse> {
+    // mentions are often followed by punctuation, eg "!so @someone, @someone_else"
+    let mut targets: Vec<String> = tokenize(&ctx.args)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|token| normalize_login(&token.text))
+        .collect();
+    // with nobody to shout out, the caller gets the shout out
+    if targets.is_empty() {
+        targets.push(ctx.sender.login.clone());
+    }
+    targets
+        .iter()
+        .map(|word| format!("https://twitch.tv/{}", word).into())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irc;
+
+    fn commander(static_commands: &str) -> HardCodedCommander {
+        HardCodedCommander {
+            static_commands_file: String::new(),
+            static_commands: StaticCommands::from_yaml(static_commands).unwrap(),
+            counts: HashMap::new(),
+            started: Instant::now(),
+        }
+    }
+
+    fn run(commander: &mut HardCodedCommander, command: &str, args: &str) -> Vec<String> {
+        let msg = irc::privmsg("viewer", "uuayn", &format!("!{} {}", command, args));
+        let ctx = CommandContext::new(&msg, "!", command, args);
+        commander
+            .handle_msg(&ctx)
+            .unwrap()
+            .into_iter()
+            .map(|response| match response {
+                CommandResponse::Say(message) => message,
+                response => panic!("unexpected response {:?}", response),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn static_commands_replace_built_in_ones() {
+        let mut commander =
+            commander("meow:\n  response: purr\n  description: pet the bot\n  role: subscriber\n");
+        assert_eq!(run(&mut commander, "meow", ""), vec!["purr"]);
+        assert_eq!(commander.required_role("meow"), Role::Subscriber);
+        assert_eq!(
+            commander.description("meow").as_deref(),
+            Some("pet the bot")
+        );
+        assert_eq!(run(&mut commander, "woof", ""), vec!["meow"]);
+    }
+
+    #[test]
+    fn shoutouts_link_the_mentioned_users() {
+        let mut commander = commander("{}");
+        assert_eq!(
+            run(&mut commander, "so", "@Someone, @someone_else"),
+            vec![
+                "https://twitch.tv/someone",
+                "https://twitch.tv/someone_else"
+            ]
+        );
+        assert_eq!(
+            run(&mut commander, "shoutout", ""),
+            vec!["https://twitch.tv/viewer"]
+        );
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+
+use crate::commander::args::Signature;
+use crate::error::Result;
+
+pub mod args;
+
+mod channels;
+pub use channels::ChannelCommander;
+
+mod composer;
+pub use composer::CommanderComposer;
+pub use composer::UnauthorizedPolicy;
+pub use composer::DEFAULT_PREFIX;
+
+mod context;
+pub use context::CommandContext;
+
+mod cooldown;
+pub use cooldown::Cooldown;
+pub use cooldown::CooldownTracker;
+pub use cooldown::Reservation;
+
+mod counters;
+pub use counters::CounterCommander;
+pub use counters::Counters;
+
+mod custom;
+pub use custom::CustomCommander;
+
+mod files;
+
+mod hard_coded;
+pub use hard_coded::HardCodedCommander;
+
+mod quotes;
+pub use quotes::Quote;
+pub use quotes::QuoteCommander;
+
+mod registry;
+pub use registry::CommandRegistry;
+
+mod response;
+pub use response::CommandResponse;
+
+mod role;
+pub use role::Role;
+
+mod static_commands;
+pub use static_commands::Responses;
+pub use static_commands::StaticCommand;
+pub use static_commands::StaticCommands;
+
+pub mod template;
+
+mod triggers;
+pub use triggers::Trigger;
+pub use triggers::Triggers;
+
+pub trait IrcCommander {
+    fn handle_msg(&mut self, ctx: &CommandContext) -> Option<Vec<CommandResponse>>;
+    fn get_commands(&self) -> Vec<String>;
+
+    /// Name a channel's configuration enables the commander by, eg `quotes`.
+    fn name(&self) -> &'static str;
+
+    /// Minimum role a chatter must have to invoke the given command.
+    fn required_role(&self, _cmd: &str) -> Role {
+        Role::Everyone
+    }
+
+    /// Name of the command `cmd` invokes, eg the command an alias stands for. Cooldowns are kept
+    /// per command name, so they're shared by its aliases.
+    fn command_name(&self, cmd: &str) -> String {
+        cmd.to_string()
+    }
+
+    /// Cooldowns applied to the given command unless overridden in `CommanderComposer`.
+    fn cooldown(&self, _cmd: &str) -> Cooldown {
+        Cooldown::default()
+    }
+
+    /// Parameters of the given command. `CommanderComposer` replies with the usage instead of
+    /// invoking the command when its arguments don't fit.
+    fn signature(&self, _cmd: &str) -> Option<Signature> {
+        None
+    }
+
+    /// What the given command does, shown by `!help <command>`.
+    fn description(&self, _cmd: &str) -> Option<String> {
+        None
+    }
+
+    /// How the given command is invoked, starting with `prefix`, eg `!addquote <author> <text...>`.
+    fn usage(&self, cmd: &str, prefix: &str) -> String {
+        match self.signature(cmd) {
+            Some(signature) => signature.usage(prefix),
+            None => format!("{}{}", prefix, cmd),
+        }
+    }
+
+    /// Files this commander loads its command definitions from. `CommanderComposer` watches these
+    /// and calls `reload` when any of them change.
+    fn watched_files(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Reload command definitions from disk. Implementations must keep their previous definitions
+    /// if the reload fails.
+    fn reload(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Commander whose handlers may perform I/O. `CommanderComposer` runs handlers concurrently, so
+/// implementations that keep state need interior mutability.
+#[async_trait]
+pub trait AsyncIrcCommander: Send + Sync {
+    async fn handle_msg(&self, ctx: &CommandContext) -> Option<Vec<CommandResponse>>;
+    fn get_commands(&self) -> Vec<String>;
+    fn name(&self) -> &'static str;
+
+    fn required_role(&self, _cmd: &str) -> Role {
+        Role::Everyone
+    }
+
+    fn command_name(&self, cmd: &str) -> String {
+        cmd.to_string()
+    }
+
+    fn cooldown(&self, _cmd: &str) -> Cooldown {
+        Cooldown::default()
+    }
+
+    fn signature(&self, _cmd: &str) -> Option<Signature> {
+        None
+    }
+
+    fn description(&self, _cmd: &str) -> Option<String> {
+        None
+    }
+
+    fn usage(&self, cmd: &str, prefix: &str) -> String {
+        match self.signature(cmd) {
+            Some(signature) => signature.usage(prefix),
+            None => format!("{}{}", prefix, cmd),
+        }
+    }
+
+    fn watched_files(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    fn reload(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Adapts an `IrcCommander` for use with `CommanderComposer`.
+///
+/// Handlers run one at a time with the commander locked. The metadata of its commands is kept
+/// aside and refreshed after every handler and reload, so `CommanderComposer` can look it up
+/// without waiting for a running handler.
+pub struct SyncCommander<C: IrcCommander + Send> {
+    name: &'static str,
+    inner: Mutex<C>,
+    metadata: Mutex<Arc<Metadata>>,
+}
+
+impl<C: IrcCommander + Send> SyncCommander<C> {
+    pub fn new(commander: C) -> Self {
+        Self {
+            name: commander.name(),
+            metadata: Mutex::new(Arc::new(Metadata::of(&commander))),
+            inner: Mutex::new(commander),
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, C> {
+        // a panicking handler doesn't leave the commander in a state worth refusing to use
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn metadata(&self) -> Arc<Metadata> {
+        self.metadata
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn refresh_metadata(&self, commander: &C) {
+        let metadata = Arc::new(Metadata::of(commander));
+        *self.metadata.lock().unwrap_or_else(|e| e.into_inner()) = metadata;
+    }
+
+    fn info(&self, cmd: &str) -> Option<CommandInfo> {
+        self.metadata().info.get(cmd).cloned()
+    }
+}
+
+/// What `SyncCommander` keeps of the commands of a commander.
+struct Metadata {
+    commands: Vec<String>,
+    info: HashMap<String, CommandInfo>,
+    watched_files: Vec<PathBuf>,
+}
+
+#[derive(Clone)]
+struct CommandInfo {
+    role: Role,
+    name: String,
+    cooldown: Cooldown,
+    signature: Option<Signature>,
+    description: Option<String>,
+    // usage without the prefix, which is prepended when asked for
+    usage: String,
+}
+
+impl Metadata {
+    fn of<C: IrcCommander>(commander: &C) -> Self {
+        let commands = commander.get_commands();
+        let info = commands
+            .iter()
+            .map(|cmd| {
+                let info = CommandInfo {
+                    role: commander.required_role(cmd),
+                    name: commander.command_name(cmd),
+                    cooldown: commander.cooldown(cmd),
+                    signature: commander.signature(cmd),
+                    description: commander.description(cmd),
+                    usage: commander.usage(cmd, ""),
+                };
+                (cmd.clone(), info)
+            })
+            .collect();
+        Self {
+            commands,
+            info,
+            watched_files: commander.watched_files(),
+        }
+    }
+}
+
+#[async_trait]
+impl<C: IrcCommander + Send> AsyncIrcCommander for SyncCommander<C> {
+    async fn handle_msg(&self, ctx: &CommandContext) -> Option<Vec<CommandResponse>> {
+        let mut commander = self.inner();
+        let responses = commander.handle_msg(ctx);
+        // the command may have added or removed commands, eg !addcom
+        self.refresh_metadata(&commander);
+        responses
+    }
+
+    fn get_commands(&self) -> Vec<String> {
+        self.metadata().commands.clone()
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn required_role(&self, cmd: &str) -> Role {
+        self.info(cmd).map_or(Role::Everyone, |info| info.role)
+    }
+
+    fn command_name(&self, cmd: &str) -> String {
+        self.info(cmd)
+            .map_or_else(|| cmd.to_string(), |info| info.name)
+    }
+
+    fn cooldown(&self, cmd: &str) -> Cooldown {
+        self.info(cmd).map(|info| info.cooldown).unwrap_or_default()
+    }
+
+    fn signature(&self, cmd: &str) -> Option<Signature> {
+        self.info(cmd)?.signature
+    }
+
+    fn description(&self, cmd: &str) -> Option<String> {
+        self.info(cmd)?.description
+    }
+
+    fn usage(&self, cmd: &str, prefix: &str) -> String {
+        match self.info(cmd) {
+            Some(info) => format!("{}{}", prefix, info.usage),
+            None => format!("{}{}", prefix, cmd),
+        }
+    }
+
+    fn watched_files(&self) -> Vec<PathBuf> {
+        self.metadata().watched_files.clone()
+    }
+
+    fn reload(&self) -> Result<()> {
+        let mut commander = self.inner();
+        let reloaded = commander.reload();
+        self.refresh_metadata(&commander);
+        reloaded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irc;
+
+    // remembers the commands added with !add
+    struct Adder {
+        added: Vec<String>,
+    }
+
+    impl IrcCommander for Adder {
+        fn name(&self) -> &'static str {
+            "adder"
+        }
+
+        fn get_commands(&self) -> Vec<String> {
+            let mut commands = vec!["add".to_string()];
+            commands.extend(self.added.iter().cloned());
+            commands
+        }
+
+        fn required_role(&self, cmd: &str) -> Role {
+            match cmd {
+                "add" => Role::Moderator,
+                _ => Role::Everyone,
+            }
+        }
+
+        fn description(&self, cmd: &str) -> Option<String> {
+            Some(format!("{} something", cmd))
+        }
+
+        fn handle_msg(&mut self, ctx: &CommandContext) -> Option<Vec<CommandResponse>> {
+            self.added.push(ctx.args.clone());
+            Some(Vec::new())
+        }
+    }
+
+    #[test]
+    fn metadata_is_available_while_a_handler_runs() {
+        let commander = SyncCommander::new(Adder { added: Vec::new() });
+        let _running = commander.inner();
+        assert_eq!(commander.required_role("add"), Role::Moderator);
+        assert_eq!(commander.usage("add", "?"), "?add");
+        assert_eq!(
+            commander.description("add").as_deref(),
+            Some("add something")
+        );
+        assert_eq!(commander.description("nothing"), None);
+    }
+
+    #[tokio::test]
+    async fn metadata_follows_the_commands_handled() {
+        let commander = SyncCommander::new(Adder { added: Vec::new() });
+        let msg = irc::privmsg("uuayn", "uuayn", "!add hello");
+        let ctx = CommandContext::new(&msg, "!", "add", "hello");
+        commander.handle_msg(&ctx).await.unwrap();
+        assert_eq!(commander.get_commands(), vec!["add", "hello"]);
+        assert_eq!(commander.usage("hello", "!"), "!hello");
+    }
+}
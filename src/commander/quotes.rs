@@ -0,0 +1,337 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::commander::args::{ArgKind, Signature};
+use crate::commander::files::write_atomically;
+use crate::commander::{CommandContext, CommandResponse, IrcCommander, Role};
+use crate::error::Result;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Quote {
+    /// Assigned on import when missing or already taken.
+    #[serde(default)]
+    pub id: u64,
+    pub text: String,
+    pub author: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<NaiveDate>,
+    /// Game or category being streamed when the quote was added.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub added_by: Option<String>,
+}
+
+impl Quote {
+    /// Whether `other` is the same quote, regardless of ID and when it was added.
+    fn same_as(&self, other: &Quote) -> bool {
+        self.text.trim() == other.text.trim()
+            && self.author.trim().eq_ignore_ascii_case(other.author.trim())
+    }
+
+    fn matches(&self, keywords: &[String]) -> bool {
+        let haystack = format!(
+            "{} {} {}",
+            self.text,
+            self.author,
+            self.game.as_deref().unwrap_or_default()
+        )
+        .to_lowercase();
+        keywords.iter().all(|keyword| haystack.contains(keyword))
+    }
+}
+
+impl std::fmt::Display for Quote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}: \"{}\" - {}", self.id, self.text, self.author)?;
+        match (&self.game, &self.date) {
+            (Some(game), Some(date)) => write!(f, " ({}, {})", game, date),
+            (Some(game), None) => write!(f, " ({})", game),
+            (None, Some(date)) => write!(f, " ({})", date),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct QuoteBook {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    game: Option<String>,
+    #[serde(default)]
+    quotes: Vec<Quote>,
+}
+
+impl QuoteBook {
+    fn next_id(&self) -> u64 {
+        self.quotes.iter().map(|quote| quote.id).max().unwrap_or(0) + 1
+    }
+
+    fn add(&mut self, mut quote: Quote) -> u64 {
+        if quote.id == 0 || self.quotes.iter().any(|q| q.id == quote.id) {
+            quote.id = self.next_id();
+        }
+        let id = quote.id;
+        self.quotes.push(quote);
+        id
+    }
+
+    /// Add the `quotes` not already in the book, returning how many were added.
+    fn import(&mut self, quotes: Vec<Quote>) -> usize {
+        let mut count = 0;
+        for quote in quotes.into_iter() {
+            if !self.quotes.iter().any(|q| q.same_as(&quote)) {
+                self.add(quote);
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+/// Viewer quotes:
+///
+/// * `!quote` - random quote
+/// * `!quote 12` - quote #12
+/// * `!quote <keywords>` - random quote containing all keywords
+/// * `!addquote <author> <text>` - add a quote attributed to `author`
+/// * `!delquote 12` - delete quote #12
+/// * `!quotegame <game>` - set the game or category recorded with new quotes
+///
+/// Quotes are saved to `quotes_file` after every change.
+pub struct QuoteCommander {
+    quotes_file: PathBuf,
+    book: QuoteBook,
+}
+
+impl QuoteCommander {
+    /// Load quotes from `quotes_file`, starting with none if it doesn't exist yet.
+    pub fn new(quotes_file: PathBuf) -> Result<Self> {
+        let book = if quotes_file.exists() {
+            serde_yaml::from_str(&read_file(&quotes_file)?)?
+        } else {
+            QuoteBook::default()
+        };
+        Ok(Self { quotes_file, book })
+    }
+
+    /// Add the quotes from a YAML list of quotes, eg exported from another bot. Quotes already
+    /// present, going by their text and author, are skipped, so importing the same file twice
+    /// is harmless. Quotes without an ID or whose ID is already taken get a new one. Returns the
+    /// number of quotes imported.
+    pub fn import_yaml<P: AsRef<Path>>(&mut self, path: P) -> Result<usize> {
+        let quotes: Vec<Quote> = serde_yaml::from_str(&read_file(path.as_ref())?)?;
+        let count = self.book.import(quotes);
+        self.book.quotes.sort_by_key(|quote| quote.id);
+        self.save()?;
+        Ok(count)
+    }
+
+    /// Write all quotes to `path` as a YAML list of quotes.
+    pub fn export_yaml<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        write_atomically(path.as_ref(), &serde_yaml::to_string(&self.book.quotes)?)
+    }
+
+    fn save(&self) -> Result<()> {
+        write_atomically(&self.quotes_file, &serde_yaml::to_string(&self.book)?)
+    }
+
+    fn save_and_reply(&self, reply: String) -> CommandResponse {
+        if let Err(e) = self.save() {
+            println!(
+                "failed to save quotes to {}: {}",
+                self.quotes_file.display(),
+                e
+            );
+            return CommandResponse::Reply(format!("{}, but saving it failed: {}", reply, e));
+        }
+        CommandResponse::Reply(reply)
+    }
+
+    fn quote(&self, ctx: &CommandContext) -> CommandResponse {
+        let args = ctx.args.trim();
+        let quote = if args.is_empty() {
+            self.book.quotes.choose(&mut rand::thread_rng())
+        } else if let Ok(id) = args.trim_start_matches('#').parse::<u64>() {
+            self.book.quotes.iter().find(|quote| quote.id == id)
+        } else {
+            let keywords: Vec<String> = args
+                .split_whitespace()
+                .map(|keyword| keyword.to_lowercase())
+                .collect();
+            let matching: Vec<&Quote> = self
+                .book
+                .quotes
+                .iter()
+                .filter(|quote| quote.matches(&keywords))
+                .collect();
+            matching.choose(&mut rand::thread_rng()).copied()
+        };
+        match quote {
+            Some(quote) => CommandResponse::Say(quote.to_string()),
+            None => CommandResponse::Reply("no such quote".to_string()),
+        }
+    }
+
+    fn add_quote(&mut self, ctx: &CommandContext, signature: &Signature) -> CommandResponse {
+        let args = match ctx.parse_args(signature) {
+            Ok(args) => args,
+            Err(e) => return CommandResponse::Reply(e.to_string()),
+        };
+        let id = self.book.add(Quote {
+            id: 0,
+            text: args.text("text").unwrap_or_default().to_string(),
+            author: args.user("author").unwrap_or_default().to_string(),
+            date: Some(ctx.server_timestamp.naive_utc().date()),
+            game: self.book.game.clone(),
+            added_by: Some(ctx.sender.login.clone()),
+        });
+        self.save_and_reply(format!("added quote #{}", id))
+    }
+
+    fn delete_quote(&mut self, ctx: &CommandContext) -> CommandResponse {
+        let id = match ctx.args.trim().trim_start_matches('#').parse::<u64>() {
+            Ok(id) => id,
+            Err(_) => return CommandResponse::Reply(format!("usage: {}delquote <id>", ctx.prefix)),
+        };
+        let count = self.book.quotes.len();
+        self.book.quotes.retain(|quote| quote.id != id);
+        if self.book.quotes.len() == count {
+            return CommandResponse::Reply(format!("quote #{} doesn't exist", id));
+        }
+        self.save_and_reply(format!("deleted quote #{}", id))
+    }
+
+    fn set_game(&mut self, ctx: &CommandContext) -> CommandResponse {
+        let game = ctx.args.trim();
+        self.book.game = if game.is_empty() {
+            None
+        } else {
+            Some(game.to_string())
+        };
+        match &self.book.game {
+            Some(game) => self.save_and_reply(format!("new quotes will be from {}", game)),
+            None => self.save_and_reply("new quotes won't have a game".to_string()),
+        }
+    }
+}
+
+impl IrcCommander for QuoteCommander {
+    fn name(&self) -> &'static str {
+        "quotes"
+    }
+
+    fn watched_files(&self) -> Vec<PathBuf> {
+        vec![self.quotes_file.clone()]
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        self.book = serde_yaml::from_str(&read_file(&self.quotes_file)?)?;
+        Ok(())
+    }
+
+    fn get_commands(&self) -> Vec<String> {
+        ["addquote", "delquote", "quote", "quotegame"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    fn required_role(&self, cmd: &str) -> Role {
+        match cmd {
+            "addquote" | "delquote" | "quotegame" => Role::Moderator,
+            _ => Role::Everyone,
+        }
+    }
+
+    fn signature(&self, cmd: &str) -> Option<Signature> {
+        match cmd {
+            "addquote" => Some(
+                Signature::new(cmd)
+                    .required("author", ArgKind::User)
+                    .required("text", ArgKind::Rest),
+            ),
+            "delquote" => Some(Signature::new(cmd).required("id", ArgKind::Word)),
+            "quotegame" => Some(Signature::new(cmd).optional("game", ArgKind::Rest)),
+            _ => None,
+        }
+    }
+
+    fn description(&self, cmd: &str) -> Option<String> {
+        let description = match cmd {
+            "quote" => "show a random quote, the quote with the given ID or one matching keywords",
+            "addquote" => "add a quote",
+            "delquote" => "delete a quote",
+            "quotegame" => "set the game recorded with new quotes",
+            _ => return None,
+        };
+        Some(description.to_string())
+    }
+
+    fn usage(&self, cmd: &str, prefix: &str) -> String {
+        match cmd {
+            "quote" => format!("{}quote [id|keywords...]", prefix),
+            cmd => match self.signature(cmd) {
+                Some(signature) => signature.usage(prefix),
+                None => format!("{}{}", prefix, cmd),
+            },
+        }
+    }
+
+    fn handle_msg(&mut self, ctx: &CommandContext) -> Option<Vec<CommandResponse>> {
+        let response = match ctx.command.as_str() {
+            "quote" => self.quote(ctx),
+            "addquote" => self.add_quote(ctx, &self.signature("addquote")?),
+            "delquote" => self.delete_quote(ctx),
+            "quotegame" => self.set_game(ctx),
+            _ => return None,
+        };
+        Some(vec![response])
+    }
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(id: u64, text: &str, author: &str) -> Quote {
+        Quote {
+            id,
+            text: text.to_string(),
+            author: author.to_string(),
+            date: None,
+            game: None,
+            added_by: None,
+        }
+    }
+
+    #[test]
+    fn import_skips_quotes_already_present() {
+        let mut book = QuoteBook::default();
+        let quotes = vec![quote(1, "meow", "uuayn"), quote(2, "woof", "viewer")];
+        assert_eq!(book.import(quotes.clone()), 2);
+        assert_eq!(book.import(quotes), 0);
+        assert_eq!(book.import(vec![quote(0, " meow ", "UUAYN")]), 0);
+        assert_eq!(book.quotes.len(), 2);
+    }
+
+    #[test]
+    fn import_renumbers_taken_ids() {
+        let mut book = QuoteBook::default();
+        book.import(vec![quote(1, "meow", "uuayn")]);
+        book.import(vec![quote(1, "woof", "viewer"), quote(0, "purr", "cat")]);
+        let ids: Vec<u64> = book.quotes.iter().map(|q| q.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+}
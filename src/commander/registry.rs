@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+/// Commands served by each commander of a `CommanderComposer`, kept up to date by the composer
+/// once given to it with `with_registry`. Commanders that define commands from chat, eg
+/// `!addcom`, consult it to refuse names another commander already serves.
+#[derive(Clone, Debug, Default)]
+pub struct CommandRegistry {
+    // commander name -> commands it serves
+    commands: Arc<RwLock<BTreeMap<String, Vec<String>>>>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the commander named `commander` serves `commands`, replacing those recorded
+    /// for it before.
+    pub fn update(&self, commander: &str, commands: Vec<String>) {
+        let mut registered = self.commands.write().unwrap_or_else(|e| e.into_inner());
+        registered.insert(commander.to_string(), commands);
+    }
+
+    /// Name of a commander other than `except` that serves `cmd`, if any.
+    pub fn served_by(&self, cmd: &str, except: &str) -> Option<String> {
+        let registered = self.commands.read().unwrap_or_else(|e| e.into_inner());
+        registered
+            .iter()
+            .find(|(commander, commands)| {
+                commander.as_str() != except && commands.iter().any(|c| c == cmd)
+            })
+            .map(|(commander, _)| commander.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn served_by_skips_the_asking_commander() {
+        let registry = CommandRegistry::new();
+        registry.update("hard_coded", vec!["meow".to_string()]);
+        registry.update("custom", vec!["lurk".to_string()]);
+        assert_eq!(
+            registry.served_by("meow", "custom"),
+            Some("hard_coded".to_string())
+        );
+        assert_eq!(registry.served_by("lurk", "custom"), None);
+        assert_eq!(registry.served_by("hug", "custom"), None);
+
+        registry.update("hard_coded", Vec::new());
+        assert_eq!(registry.served_by("meow", "custom"), None);
+    }
+}
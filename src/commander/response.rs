@@ -0,0 +1,71 @@
+use crate::commander::CommandContext;
+use crate::irc::{
+    ChatMessage, ComponentMessage, JoinChannelMessage, ModerationAction, ModerationMessage,
+    PartChannelMessage, ReplyMessage, WhisperMessage,
+};
+
+/// Something a commander wants done in response to a command.
+#[derive(Clone, Debug)]
+pub enum CommandResponse {
+    /// Chat message in the channel the command was invoked in.
+    Say(String),
+    /// Chat message threaded as a reply to the invoking message.
+    Reply(String),
+    /// `/me` action in the channel the command was invoked in.
+    Action(String),
+    Whisper {
+        user: String,
+        message: String,
+    },
+    JoinChannel(String),
+    PartChannel(String),
+    /// Moderation action in the channel the command was invoked in.
+    Moderate(ModerationAction),
+    /// The command was handled but has no visible output, eg it only had side effects.
+    Silent,
+}
+
+impl CommandResponse {
+    pub fn into_component_message(self, ctx: &CommandContext) -> Option<ComponentMessage> {
+        let channel = ctx.channel_login.clone();
+        let message = match self {
+            CommandResponse::Say(message) => {
+                ComponentMessage::Chat(ChatMessage { channel, message })
+            }
+            CommandResponse::Reply(message) => ComponentMessage::Reply(ReplyMessage {
+                channel,
+                reply_to_id: ctx.message_id.clone(),
+                message,
+            }),
+            CommandResponse::Action(message) => {
+                ComponentMessage::Action(ChatMessage { channel, message })
+            }
+            CommandResponse::Whisper { user, message } => {
+                ComponentMessage::Whisper(WhisperMessage { user, message })
+            }
+            CommandResponse::JoinChannel(channel) => {
+                ComponentMessage::JoinChannel(JoinChannelMessage { channel })
+            }
+            CommandResponse::PartChannel(channel) => {
+                ComponentMessage::PartChannel(PartChannelMessage { channel })
+            }
+            CommandResponse::Moderate(action) => {
+                ComponentMessage::Moderate(ModerationMessage { channel, action })
+            }
+            CommandResponse::Silent => return None,
+        };
+        Some(message)
+    }
+}
+
+impl From<String> for CommandResponse {
+    fn from(message: String) -> Self {
+        CommandResponse::Say(message)
+    }
+}
+
+impl From<&str> for CommandResponse {
+    fn from(message: &str) -> Self {
+        CommandResponse::Say(message.to_string())
+    }
+}
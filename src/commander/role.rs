@@ -0,0 +1,62 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use twitch_irc::message::{Badge, PrivmsgMessage};
+
+/// Chatter roles ordered from least to most privileged, so a chatter may invoke a command if their
+/// role is greater than or equal to the command's required role.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Everyone,
+    Subscriber,
+    Vip,
+    Moderator,
+    Broadcaster,
+}
+
+// by hand, `#[default]` on enum variants needs Rust 1.62
+#[allow(clippy::derivable_impls)]
+impl Default for Role {
+    fn default() -> Self {
+        Role::Everyone
+    }
+}
+
+impl Role {
+    pub fn is_everyone(&self) -> bool {
+        *self == Role::Everyone
+    }
+
+    /// Highest role indicated by the given badges.
+    pub fn from_badges(badges: &[Badge]) -> Self {
+        badges
+            .iter()
+            .map(|badge| match badge.name.as_str() {
+                "broadcaster" => Role::Broadcaster,
+                "moderator" => Role::Moderator,
+                "vip" => Role::Vip,
+                "subscriber" | "founder" => Role::Subscriber,
+                _ => Role::Everyone,
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    pub fn of_sender(msg: &PrivmsgMessage) -> Self {
+        Self::from_badges(&msg.badges)
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Role::Everyone => "everyone",
+            Role::Subscriber => "subscribers",
+            Role::Vip => "VIPs",
+            Role::Moderator => "moderators",
+            Role::Broadcaster => "the broadcaster",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::commander::files::write_atomically;
+use crate::commander::template::{self, TemplateVars};
+use crate::commander::{CommandContext, CommandResponse, Cooldown, Role};
+use crate::error::{Error, Result};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Responses {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Responses {
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            Responses::One(response) => vec![response.clone()],
+            Responses::Many(responses) => responses.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StaticCommand {
+    #[serde(alias = "response")]
+    pub responses: Responses,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Minimum role required to invoke this command.
+    #[serde(default, skip_serializing_if = "Role::is_everyone")]
+    pub role: Role,
+    #[serde(default, skip_serializing_if = "Cooldown::is_disabled")]
+    pub cooldown: Cooldown,
+    /// What the command does, shown by `!help <command>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Arguments the command takes, eg `<user>`, shown by `!help <command>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<String>,
+}
+
+impl StaticCommand {
+    pub fn new(response: String) -> Self {
+        Self {
+            responses: Responses::One(response),
+            aliases: Vec::new(),
+            role: Role::Everyone,
+            cooldown: Cooldown::default(),
+            description: None,
+            usage: None,
+        }
+    }
+
+    /// How the command is invoked, eg `!so <user>`.
+    pub fn usage(&self, prefix: &str, name: &str) -> String {
+        match &self.usage {
+            Some(usage) => format!("{}{} {}", prefix, name, usage),
+            None => format!("{}{}", prefix, name),
+        }
+    }
+
+    /// Expand this command's response templates for the given invocation.
+    pub fn render(&self, ctx: &CommandContext, vars: &TemplateVars) -> Vec<CommandResponse> {
+        self.responses
+            .to_vec()
+            .iter()
+            .map(|response| CommandResponse::Say(template::expand(response, ctx, vars)))
+            .collect()
+    }
+}
+
+/// Command definitions loaded from a YAML file mapping command names to their response(s).
+/// Responses are templates, see `template::expand` for the available variables. Eg:
+///
+/// ```yaml
+/// project:
+///   response: https://github.com/waynr/twitchy-mcbotface
+/// lurk:
+///   response: "enjoy the lurk!"
+///   role: subscriber
+///   cooldown:
+///     per_user: 300
+/// so:
+///   aliases: [shoutout]
+///   response: "go check out {target} at https://twitch.tv/{target}"
+///   description: shout out another streamer
+///   usage: <user>
+/// hug:
+///   aliases: [hugs, squeeze]
+///   responses:
+///     - "*{user} hugs chat*"
+///     - "chat has been hugged {count} times <3"
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct StaticCommands {
+    commands: BTreeMap<String, StaticCommand>,
+    // alias -> command name
+    aliases: HashMap<String, String>,
+}
+
+impl StaticCommands {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut file = File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Self::from_yaml(&contents)
+    }
+
+    pub fn from_yaml(contents: &str) -> Result<Self> {
+        let commands: BTreeMap<String, StaticCommand> = serde_yaml::from_str(contents)?;
+        Self::from_commands(commands)
+    }
+
+    fn from_commands(commands: BTreeMap<String, StaticCommand>) -> Result<Self> {
+        let mut aliases = HashMap::new();
+        for (name, command) in commands.iter() {
+            for alias in command.aliases.iter() {
+                if commands.contains_key(alias) {
+                    return Err(Error::InvalidCommandDefinition(format!(
+                        "alias '{}' of '{}' shadows an existing command",
+                        alias, name
+                    )));
+                }
+                if let Some(other) = aliases.insert(alias.clone(), name.clone()) {
+                    return Err(Error::InvalidCommandDefinition(format!(
+                        "alias '{}' is defined for both '{}' and '{}'",
+                        alias, other, name
+                    )));
+                }
+            }
+        }
+
+        Ok(Self { commands, aliases })
+    }
+
+    /// Name of the command `cmd` refers to, resolving aliases.
+    pub fn canonical_name<'a>(&'a self, cmd: &'a str) -> Option<&'a str> {
+        match self.aliases.get(cmd) {
+            Some(name) => Some(name),
+            None if self.commands.contains_key(cmd) => Some(cmd),
+            None => None,
+        }
+    }
+
+    pub fn get(&self, cmd: &str) -> Option<&StaticCommand> {
+        match self.aliases.get(cmd) {
+            Some(name) => self.commands.get(name),
+            None => self.commands.get(cmd),
+        }
+    }
+
+    /// Names of all defined commands, including aliases.
+    pub fn names(&self) -> Vec<String> {
+        self.commands
+            .keys()
+            .chain(self.aliases.keys())
+            .cloned()
+            .collect()
+    }
+
+    /// Add or replace a command definition.
+    pub fn insert(&mut self, name: String, command: StaticCommand) -> Result<()> {
+        let mut commands = self.commands.clone();
+        commands.insert(name, command);
+        *self = Self::from_commands(commands)?;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<StaticCommand> {
+        let removed = self.commands.remove(name)?;
+        self.aliases.retain(|_, command| command != name);
+        Some(removed)
+    }
+
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(&self.commands)?)
+    }
+
+    /// Write the command definitions to `path`, replacing the file only once they have been
+    /// written out completely.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        write_atomically(path.as_ref(), &self.to_yaml()?)
+    }
+}
@@ -0,0 +1,142 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::commander::args::{normalize_login, tokenize};
+use crate::commander::CommandContext;
+
+/// Values available to templates besides those taken from the invoking message.
+pub struct TemplateVars {
+    /// Number of times the command has been invoked, including this time.
+    pub count: u64,
+    /// How long the bot has been running. Not the stream's uptime: the bot may well be started
+    /// before going live, or restarted during the stream.
+    pub uptime: Duration,
+}
+
+/// Expand the variables in a response template:
+///
+/// * `{user}` - display name of the chatter who invoked the command
+/// * `{channel}` - channel the command was invoked in
+/// * `{arg1}`, `{arg2}`, ... - individual command arguments, words in double quotes counting as
+///   one, empty if not given
+/// * `{args}` - all command arguments
+/// * `{target}` - first argument as a login, eg `@User,` becomes `user`, or the invoking
+///   chatter's login
+/// * `{count}` - number of times the command has been invoked
+/// * `{random:1-100}` - random number in the given inclusive range, the bounds may be negative,
+///   eg `{random:-10-10}`
+/// * `{uptime}` - how long the bot has been running, which isn't necessarily how long the stream
+///   has been live
+///
+/// Unknown variables are left as they are.
+pub fn expand(template: &str, ctx: &CommandContext, vars: &TemplateVars) -> String {
+    // quoted arguments count as one, but a stray quote shouldn't break the response
+    let args: Vec<String> = match tokenize(&ctx.args) {
+        Ok(tokens) => tokens.into_iter().map(|token| token.text).collect(),
+        Err(_) => ctx.args.split_whitespace().map(str::to_string).collect(),
+    };
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let after_brace = &rest[start + 1..];
+        let end = match after_brace.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        expanded.push_str(&rest[..start]);
+        let name = &after_brace[..end];
+        match expand_variable(name, ctx, vars, &args) {
+            Some(value) => expanded.push_str(&value),
+            None => expanded.push_str(&rest[start..start + end + 2]),
+        }
+        rest = &after_brace[end + 1..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+fn expand_variable(
+    name: &str,
+    ctx: &CommandContext,
+    vars: &TemplateVars,
+    args: &[String],
+) -> Option<String> {
+    let value = match name {
+        "user" => ctx.sender.name.clone(),
+        "channel" => ctx.channel_login.clone(),
+        "args" => args.join(" "),
+        "target" => match args.first() {
+            Some(arg) => normalize_login(arg).unwrap_or_else(|| arg.to_string()),
+            None => ctx.sender.login.clone(),
+        },
+        "count" => vars.count.to_string(),
+        "uptime" => format_duration(vars.uptime),
+        _ => {
+            if let Some(index) = name.strip_prefix("arg") {
+                let index: usize = index.parse().ok()?;
+                if index == 0 {
+                    return None;
+                }
+                args.get(index - 1).cloned().unwrap_or_default()
+            } else if let Some(range) = name.strip_prefix("random:") {
+                let (low, high) = parse_range(range)?;
+                if low > high {
+                    return None;
+                }
+                rand::thread_rng().gen_range(low..=high).to_string()
+            } else {
+                return None;
+            }
+        }
+    };
+    Some(value)
+}
+
+/// Parse the bounds of `{random:low-high}`.
+fn parse_range(range: &str) -> Option<(i64, i64)> {
+    // the bounds may be negative themselves, so try each `-` as the separator
+    range.match_indices('-').find_map(|(index, _)| {
+        let low = range[..index].trim().parse().ok()?;
+        let high = range[index + 1..].trim().parse().ok()?;
+        Some((low, high))
+    })
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, (secs / 60) % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_bounds() {
+        assert_eq!(parse_range("1-100"), Some((1, 100)));
+        assert_eq!(parse_range(" 1 - 6 "), Some((1, 6)));
+        assert_eq!(parse_range("-10-10"), Some((-10, 10)));
+        assert_eq!(parse_range("-10--5"), Some((-10, -5)));
+        assert_eq!(parse_range("1-"), None);
+        assert_eq!(parse_range("one-two"), None);
+        assert_eq!(parse_range("5"), None);
+    }
+
+    #[test]
+    fn format_durations() {
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m 5s");
+        assert_eq!(
+            format_duration(Duration::from_secs(3 * 3600 + 120)),
+            "3h 2m"
+        );
+    }
+}
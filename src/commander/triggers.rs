@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::commander::template::TemplateVars;
+use crate::commander::{CommandContext, CommandResponse, Cooldown, Responses, Role, StaticCommand};
+use crate::error::{Error, Result};
+
+#[derive(Clone, Debug, Deserialize)]
+struct TriggerDefinition {
+    name: String,
+    /// Regex matched anywhere in the message.
+    #[serde(default)]
+    pattern: Option<String>,
+    /// Words or phrases of which any one appearing in the message fires the trigger, regardless
+    /// of case and punctuation.
+    #[serde(default)]
+    keywords: Vec<String>,
+    #[serde(alias = "response")]
+    responses: Responses,
+    #[serde(default)]
+    role: Role,
+    #[serde(default)]
+    cooldown: Cooldown,
+}
+
+pub struct Trigger {
+    pub name: String,
+    pattern: Option<Regex>,
+    // normalized, see `normalize`
+    keywords: Vec<String>,
+    command: StaticCommand,
+}
+
+impl Trigger {
+    pub fn role(&self) -> Role {
+        self.command.role
+    }
+
+    pub fn cooldown(&self) -> Cooldown {
+        self.command.cooldown
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        if let Some(pattern) = &self.pattern {
+            if pattern.is_match(text) {
+                return true;
+            }
+        }
+        if self.keywords.is_empty() {
+            return false;
+        }
+        // padded so keywords only match whole words, "hi" shouldn't fire on "this"
+        let text = format!(" {} ", normalize(text));
+        self.keywords
+            .iter()
+            .any(|keyword| text.contains(&format!(" {} ", keyword)))
+    }
+}
+
+/// Lowercase words of `text` separated by single spaces, so "What  KEYBOARD?!" matches the
+/// keyword "what keyboard".
+fn normalize(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Responses to messages matching a regex or containing a keyword, without needing a command
+/// prefix, eg:
+///
+/// ```yaml
+/// - name: keyboard
+///   pattern: "(?i)what (keyboard|kb)"
+///   response: "@{user} it's a split keyboard, ask me about it!"
+///   cooldown:
+///     global: 120
+/// - name: greeting
+///   keywords: [hello, hi, hey]
+///   response: "hey {user}!"
+///   role: subscriber
+/// ```
+///
+/// Responses are templates, see `template::expand`, with `{args}` being the whole message.
+pub struct Triggers {
+    path: PathBuf,
+    triggers: Vec<Trigger>,
+    counts: HashMap<String, u64>,
+    started: Instant,
+}
+
+impl Triggers {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let triggers = load_triggers(&path)?;
+        Ok(Self {
+            path,
+            triggers,
+            counts: HashMap::new(),
+            started: Instant::now(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reload trigger definitions, keeping the previous ones if that fails.
+    pub fn reload(&mut self) -> Result<()> {
+        self.triggers = load_triggers(&self.path)?;
+        Ok(())
+    }
+
+    /// Triggers matching the given message text, in the order they're defined.
+    pub fn matching<'a>(&'a self, text: &'a str) -> impl Iterator<Item = &'a Trigger> + 'a {
+        self.triggers
+            .iter()
+            .filter(move |trigger| trigger.matches(text))
+    }
+
+    /// Responses of the trigger with the given name, counting it as fired.
+    pub fn fire(&mut self, name: &str, ctx: &CommandContext) -> Vec<CommandResponse> {
+        let trigger = match self.triggers.iter().find(|trigger| trigger.name == name) {
+            Some(trigger) => trigger,
+            None => return Vec::new(),
+        };
+        let count = self.counts.entry(name.to_string()).or_insert(0);
+        *count += 1;
+        let vars = TemplateVars {
+            count: *count,
+            uptime: self.started.elapsed(),
+        };
+        trigger.command.render(ctx, &vars)
+    }
+}
+
+fn load_triggers(path: &Path) -> Result<Vec<Trigger>> {
+    let mut file = File::open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let definitions: Vec<TriggerDefinition> = serde_yaml::from_str(&contents)?;
+
+    let mut triggers = Vec::new();
+    for definition in definitions.into_iter() {
+        if definition.pattern.is_none() && definition.keywords.is_empty() {
+            return Err(Error::InvalidCommandDefinition(format!(
+                "trigger '{}' needs a pattern or keywords",
+                definition.name
+            )));
+        }
+        let pattern = match &definition.pattern {
+            Some(pattern) => Some(Regex::new(pattern).map_err(|e| {
+                Error::InvalidCommandDefinition(format!(
+                    "invalid pattern for trigger '{}': {}",
+                    definition.name, e
+                ))
+            })?),
+            None => None,
+        };
+        triggers.push(Trigger {
+            name: definition.name,
+            pattern,
+            keywords: definition
+                .keywords
+                .iter()
+                .map(|keyword| normalize(keyword))
+                .filter(|keyword| !keyword.is_empty())
+                .collect(),
+            command: StaticCommand {
+                responses: definition.responses,
+                aliases: Vec::new(),
+                role: definition.role,
+                cooldown: definition.cooldown,
+                description: None,
+                usage: None,
+            },
+        });
+    }
+    Ok(triggers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(keywords: &[&str]) -> Trigger {
+        Trigger {
+            name: "test".to_string(),
+            pattern: None,
+            keywords: keywords.iter().map(|keyword| normalize(keyword)).collect(),
+            command: StaticCommand::new(String::new()),
+        }
+    }
+
+    #[test]
+    fn keywords_match_whole_words() {
+        let trigger = trigger(&["hi", "hello"]);
+        assert!(trigger.matches("hi chat"));
+        assert!(trigger.matches("oh, HELLO!"));
+        assert!(!trigger.matches("this is it"));
+    }
+
+    #[test]
+    fn keywords_match_phrases() {
+        let trigger = trigger(&["What keyboard"]);
+        assert!(trigger.matches("what keyboard is that?"));
+        assert!(trigger.matches("uhh...  What  KEYBOARD?!"));
+        assert!(!trigger.matches("what a keyboard"));
+    }
+}
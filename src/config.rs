@@ -0,0 +1,465 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::irc::{DropPolicy, QueuePolicy};
+
+const APP_NAME: &str = "twitchy-mcbotface";
+const CONFIG_FILE_NAME: &str = "config.yml";
+
+/// Overrides the location of the configuration file.
+pub const CONFIG_ENV_VAR: &str = "TWITCHY_MCBOTFACE_CONFIG";
+/// Overrides `credentials`.
+pub const CREDENTIALS_ENV_VAR: &str = "TWITCHY_MCBOTFACE_CREDENTIALS";
+/// Overrides `channels`, as a comma separated list.
+pub const CHANNELS_ENV_VAR: &str = "TWITCHY_MCBOTFACE_CHANNELS";
+
+/// Bot configuration, loaded from `config.yml` in the XDG config directory unless given
+/// elsewhere, eg:
+///
+/// ```yaml
+/// credentials: auth.yml
+/// owner_channel: uuayn
+/// channels:
+///   - uuayn
+///   - name: someone_else
+///     prefixes: ["?"]
+///     commanders: [hard_coded, quotes]
+/// commands:
+///   static: commands.yml
+///   triggers: triggers.yml
+///   announcements: announcements.yml
+///   diagnostics_channel: someone_else
+/// queue:
+///   max_depth: 50
+///   max_age_secs: 60
+///   drop_policy: oldest
+/// ui:
+///   fonts: [/usr/share/fonts/truetype/hack/Hack-Regular.ttf]
+///   background_image: images/background.jpg
+///   window:
+///     width: 800
+///     height: 600
+/// ndi:
+///   chatbox_source: chatbox
+/// ```
+///
+/// Relative paths are relative to the directory of the configuration file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// YAML file with the bot's login and OAuth token.
+    pub credentials: PathBuf,
+    /// Channels joined on startup, either by name or with settings of their own.
+    pub channels: Vec<ChannelConfig>,
+    /// Channel whose broadcaster may make the bot join and leave other channels, the first of
+    /// `channels` if not given.
+    pub owner_channel: Option<String>,
+    pub commands: CommandsConfig,
+    /// Outgoing message queues of each channel.
+    pub queue: QueueConfig,
+    pub ui: UiConfig,
+    pub ndi: NdiConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(from = "ChannelEntry")]
+pub struct ChannelConfig {
+    pub name: String,
+    /// Command prefixes, `!` if not given.
+    pub prefixes: Option<Vec<String>>,
+    /// Names of the commanders enabled in the channel, all of them if not given.
+    pub commanders: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChannelEntry {
+    Name(String),
+    Settings(ChannelSettings),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChannelSettings {
+    name: String,
+    #[serde(default)]
+    prefixes: Option<Vec<String>>,
+    #[serde(default)]
+    commanders: Option<Vec<String>>,
+}
+
+impl ChannelConfig {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            prefixes: None,
+            commanders: None,
+        }
+    }
+}
+
+impl From<ChannelEntry> for ChannelConfig {
+    fn from(entry: ChannelEntry) -> Self {
+        match entry {
+            ChannelEntry::Name(name) => Self::new(&name),
+            ChannelEntry::Settings(settings) => Self {
+                name: settings.name,
+                prefixes: settings.prefixes,
+                commanders: settings.commanders,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandsConfig {
+    /// Static commands, see `StaticCommands`.
+    #[serde(rename = "static")]
+    pub static_commands: PathBuf,
+    /// Commands managed from chat with `!addcom` and friends.
+    pub custom: PathBuf,
+    pub counters: PathBuf,
+    pub quotes: PathBuf,
+    pub triggers: Option<PathBuf>,
+    pub announcements: Option<PathBuf>,
+    /// Channel that reload diagnostics are also posted to, eg a mod-only channel. It has to be one
+    /// of `channels`, so the bot is in it.
+    pub diagnostics_channel: Option<String>,
+}
+
+/// See `QueuePolicy`.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueueConfig {
+    /// Maximum number of messages waiting to be sent to a channel.
+    pub max_depth: usize,
+    /// Seconds a message below moderation priority may wait before it's dropped, `~` to send it
+    /// however long it waited.
+    pub max_age_secs: Option<u64>,
+    /// Which message to drop from a full queue, `oldest` or `newest`.
+    pub drop_policy: DropPolicy,
+}
+
+impl QueueConfig {
+    pub fn policy(&self) -> QueuePolicy {
+        QueuePolicy {
+            max_depth: self.max_depth,
+            max_age: self.max_age_secs.map(Duration::from_secs),
+            drop_policy: self.drop_policy,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    /// Fonts tried in order for monospace text, before the built in FiraCode and egui's own.
+    pub fonts: Vec<PathBuf>,
+    /// Image shown behind the chat box.
+    pub background_image: Option<PathBuf>,
+    pub window: WindowConfig,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NdiConfig {
+    /// Name of the NDI source the chat box is sent as.
+    pub chatbox_source: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            credentials: PathBuf::from("auth.yml"),
+            channels: Vec::new(),
+            owner_channel: None,
+            commands: CommandsConfig::default(),
+            queue: QueueConfig::default(),
+            ui: UiConfig::default(),
+            ndi: NdiConfig::default(),
+        }
+    }
+}
+
+impl Default for CommandsConfig {
+    fn default() -> Self {
+        Self {
+            static_commands: PathBuf::from("commands.yml"),
+            custom: PathBuf::from("custom_commands.yml"),
+            counters: PathBuf::from("counters.yml"),
+            quotes: PathBuf::from("quotes.yml"),
+            triggers: None,
+            announcements: None,
+            diagnostics_channel: None,
+        }
+    }
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        let policy = QueuePolicy::default();
+        Self {
+            max_depth: policy.max_depth,
+            max_age_secs: policy.max_age.map(|max_age| max_age.as_secs()),
+            drop_policy: policy.drop_policy,
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width: 800.0,
+            height: 600.0,
+        }
+    }
+}
+
+impl Default for NdiConfig {
+    fn default() -> Self {
+        Self {
+            chatbox_source: "chatbox".to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// Where the configuration file is read from: `path` if given, eg from the command line,
+    /// else `$TWITCHY_MCBOTFACE_CONFIG`, else `twitchy-mcbotface/config.yml` in the XDG config
+    /// directory.
+    pub fn locate(path: Option<PathBuf>) -> Result<PathBuf> {
+        if let Some(path) = path {
+            return Ok(path);
+        }
+        if let Some(path) = std::env::var_os(CONFIG_ENV_VAR) {
+            return Ok(PathBuf::from(path));
+        }
+        match dirs::config_dir() {
+            Some(dir) => Ok(dir.join(APP_NAME).join(CONFIG_FILE_NAME)),
+            None => Err(Error::InvalidConfig(format!(
+                "no config directory found, set {} or pass a config file",
+                CONFIG_ENV_VAR
+            ))),
+        }
+    }
+
+    /// Locate, load and validate the configuration, see `locate`. Only a configuration file
+    /// given explicitly must exist, the defaults are used otherwise.
+    pub fn load(path: Option<PathBuf>) -> Result<Self> {
+        let explicit = path.is_some() || std::env::var_os(CONFIG_ENV_VAR).is_some();
+        let path = Self::locate(path)?;
+        let mut config = if path.exists() || explicit {
+            Self::from_file(&path)?
+        } else {
+            Self::default()
+        };
+        config.apply_env()?;
+        config.resolve_paths(path.parent().unwrap_or_else(|| Path::new(".")));
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let mut file = File::open(path).map_err(|e| {
+            Error::InvalidConfig(format!("failed to open {}: {}", path.display(), e))
+        })?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_yaml::from_str(&contents)?)
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        if let Some(credentials) = std::env::var_os(CREDENTIALS_ENV_VAR) {
+            self.credentials = PathBuf::from(credentials);
+        }
+        if let Some(channels) = std::env::var_os(CHANNELS_ENV_VAR) {
+            let channels = channels.into_string().map_err(|_| {
+                Error::InvalidConfig(format!("{} isn't valid unicode", CHANNELS_ENV_VAR))
+            })?;
+            self.channels = channels
+                .split(',')
+                .map(str::trim)
+                .filter(|channel| !channel.is_empty())
+                .map(ChannelConfig::new)
+                .collect();
+        }
+        Ok(())
+    }
+
+    fn resolve_paths(&mut self, dir: &Path) {
+        let resolve = |path: &mut PathBuf| {
+            if path.is_relative() {
+                *path = dir.join(&path);
+            }
+        };
+        resolve(&mut self.credentials);
+        resolve(&mut self.commands.static_commands);
+        resolve(&mut self.commands.custom);
+        resolve(&mut self.commands.counters);
+        resolve(&mut self.commands.quotes);
+        self.commands.triggers.iter_mut().for_each(resolve);
+        self.commands.announcements.iter_mut().for_each(resolve);
+        self.ui.fonts.iter_mut().for_each(resolve);
+        self.ui.background_image.iter_mut().for_each(resolve);
+    }
+
+    /// Check for mistakes that would otherwise only surface once the bot is running. The UI
+    /// settings are only checked by `validate_ui`, since most uses have no UI, and whether there
+    /// are any channels by `validate_channels`, since only running the bot joins them.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        for channel in self.channels.iter() {
+            let valid = !channel.name.is_empty()
+                && channel
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+            if !valid {
+                problems.push(format!(
+                    "channel \"{}\" isn't a lowercase Twitch login",
+                    channel.name
+                ));
+            }
+            if let Some(prefixes) = &channel.prefixes {
+                if prefixes.is_empty() || prefixes.iter().any(|prefix| prefix.trim().is_empty()) {
+                    problems.push(format!("channel \"{}\" has an empty prefix", channel.name));
+                }
+            }
+        }
+
+        if let Some(owner) = &self.owner_channel {
+            if !self.channels.iter().any(|channel| &channel.name == owner) {
+                problems.push(format!(
+                    "owner channel \"{}\" isn't one of the channels joined",
+                    owner
+                ));
+            }
+        }
+        if let Some(diagnostics) = &self.commands.diagnostics_channel {
+            if !self
+                .channels
+                .iter()
+                .any(|channel| &channel.name == diagnostics)
+            {
+                problems.push(format!(
+                    "diagnostics channel \"{}\" isn't one of the channels joined",
+                    diagnostics
+                ));
+            }
+        }
+
+        if self.queue.max_depth == 0 {
+            problems.push("queue max_depth is 0, nothing could be sent".to_string());
+        }
+
+        let mut required = vec![&self.credentials, &self.commands.static_commands];
+        required.extend(self.commands.triggers.iter());
+        required.extend(self.commands.announcements.iter());
+        check_files_exist(required, &mut problems);
+
+        to_result(problems)
+    }
+
+    /// The channel whose broadcaster may make the bot join and leave other channels, if any.
+    pub fn owner_channel(&self) -> Option<&str> {
+        match &self.owner_channel {
+            Some(owner) => Some(owner),
+            None => self.channels.first().map(|channel| channel.name.as_str()),
+        }
+    }
+
+    /// Check there are channels to join, for when the bot will run.
+    pub fn validate_channels(&self) -> Result<()> {
+        if self.channels.is_empty() {
+            return Err(Error::InvalidConfig("no channels to join".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Check the settings of the window and the NDI chat box, for when they will be shown.
+    pub fn validate_ui(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        let mut required: Vec<&PathBuf> = self.ui.fonts.iter().collect();
+        required.extend(self.ui.background_image.iter());
+        check_files_exist(required, &mut problems);
+
+        let window = self.ui.window;
+        if !(window.width > 0.0 && window.height > 0.0) {
+            problems.push(format!(
+                "window size {}x{} isn't positive",
+                window.width, window.height
+            ));
+        }
+        if self.ndi.chatbox_source.trim().is_empty() {
+            problems.push("NDI chat box source name is empty".to_string());
+        }
+
+        to_result(problems)
+    }
+}
+
+fn check_files_exist(paths: Vec<&PathBuf>, problems: &mut Vec<String>) {
+    for path in paths.into_iter() {
+        if !path.is_file() {
+            problems.push(format!("{} doesn't exist", path.display()));
+        }
+    }
+}
+
+fn to_result(problems: Vec<String>) -> Result<()> {
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig(problems.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ui_paths_are_only_checked_for_the_ui() {
+        let mut config = Config {
+            channels: vec![ChannelConfig::new("uuayn")],
+            credentials: PathBuf::from("Cargo.toml"),
+            ..Config::default()
+        };
+        config.commands.static_commands = PathBuf::from("Cargo.toml");
+        config.ui.fonts = vec![PathBuf::from("no/such/font.ttf")];
+        assert!(config.validate().is_ok());
+        assert!(matches!(
+            config.validate_ui(),
+            Err(Error::InvalidConfig(problems)) if problems.contains("font.ttf")
+        ));
+    }
+
+    #[test]
+    fn channels_are_only_required_to_run() {
+        let mut config = Config {
+            credentials: PathBuf::from("Cargo.toml"),
+            ..Config::default()
+        };
+        config.commands.static_commands = PathBuf::from("Cargo.toml");
+        assert!(config.validate().is_ok());
+        assert!(matches!(
+            config.validate_channels(),
+            Err(Error::InvalidConfig(problem)) if problem == "no channels to join"
+        ));
+    }
+}
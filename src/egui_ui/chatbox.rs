@@ -13,6 +13,7 @@ use twitch_irc::message::PrivmsgMessage;
 use twitch_irc::message::ServerMessage;
 
 use crate::egui_ui::BotfaceEvent;
+use crate::irc::IrcEvent;
 use crate::irc::MessageDispatcher;
 
 struct ChatMessage {
@@ -169,9 +170,9 @@ impl ChatboxDispatcher {
     }
 
     pub async fn run(&mut self) {
-        while let Ok(message) = self.message_dispatcher.receiver.recv().await {
+        while let Some(message) = self.message_dispatcher.recv().await {
             match message {
-                ServerMessage::Privmsg(msg) => {
+                IrcEvent::Message(ServerMessage::Privmsg(msg)) => {
                     match self.state.lock() {
                         Ok(mut cbstate) => {
                             (*cbstate).messages.push(msg.into());
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use egui::{Color32, Response, Ui};
+use glutin::event_loop::EventLoopProxy;
+
+use crate::egui_ui::BotfaceEvent;
+use crate::irc::{ConnectionState, IrcEvent, MessageDispatcher};
+
+pub struct ConnectionPanel {
+    state: Arc<Mutex<ConnectionState>>,
+}
+
+impl ConnectionPanel {
+    pub fn new(state: Arc<Mutex<ConnectionState>>) -> Self {
+        Self { state }
+    }
+
+    pub fn state(&self) -> Arc<Mutex<ConnectionState>> {
+        self.state.clone()
+    }
+
+    pub fn show(&self, ui: &mut Ui) -> Response {
+        let state = self.state.lock().unwrap();
+        let color = match *state {
+            ConnectionState::Connected => Color32::GREEN,
+            ConnectionState::Connecting | ConnectionState::Reconnecting { .. } => Color32::YELLOW,
+            ConnectionState::Disconnected(_) => Color32::RED,
+        };
+        ui.colored_label(color, format!("Twitch: {}", state))
+    }
+}
+
+pub struct ConnectionDispatcher {
+    message_dispatcher: MessageDispatcher,
+    state: Arc<Mutex<ConnectionState>>,
+    proxy: EventLoopProxy<BotfaceEvent>,
+}
+
+impl ConnectionDispatcher {
+    pub fn new(
+        message_dispatcher: MessageDispatcher,
+        state: Arc<Mutex<ConnectionState>>,
+        proxy: EventLoopProxy<BotfaceEvent>,
+    ) -> Self {
+        Self {
+            message_dispatcher,
+            state,
+            proxy,
+        }
+    }
+
+    pub async fn run(&mut self) {
+        // the state may have changed before subscribing
+        self.update(self.message_dispatcher.connection_state());
+        while let Some(event) = self.message_dispatcher.recv().await {
+            if let IrcEvent::Connection(state) = event {
+                self.update(state);
+            }
+        }
+    }
+
+    fn update(&self, connection_state: ConnectionState) {
+        match self.state.lock() {
+            Ok(mut state) => {
+                *state = connection_state;
+            }
+            Err(e) => eprintln!("{:?}", e),
+        }
+        if self.proxy.send_event(BotfaceEvent::Nonce).is_err() {
+            println!("failed to show connection state: event loop is gone");
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use egui::{Response, RichText, Ui};
+use glutin::event_loop::EventLoopProxy;
+use tokio::sync::watch;
+
+use crate::commander::Counters;
+use crate::egui_ui::BotfaceEvent;
+
+pub struct CountersState {
+    counters: Counters,
+}
+
+impl CountersState {
+    pub fn new() -> Self {
+        Self {
+            counters: Counters::new(),
+        }
+    }
+}
+
+pub struct CountersPanel {
+    state: Arc<Mutex<CountersState>>,
+}
+
+impl CountersPanel {
+    pub fn new(state: Arc<Mutex<CountersState>>) -> Self {
+        Self { state }
+    }
+
+    pub fn state(&self) -> Arc<Mutex<CountersState>> {
+        self.state.clone()
+    }
+
+    pub fn show(&self, ui: &mut Ui) -> Response {
+        let state = self.state.lock().unwrap();
+        ui.horizontal_wrapped(|ui| {
+            for (name, count) in state.counters.iter() {
+                ui.label(
+                    RichText::new(format!("{}: {}", name, count))
+                        .monospace()
+                        .size(18.0),
+                );
+            }
+        })
+        .response
+    }
+}
+
+pub struct CountersDispatcher {
+    receiver: watch::Receiver<Counters>,
+    state: Arc<Mutex<CountersState>>,
+    proxy: EventLoopProxy<BotfaceEvent>,
+}
+
+impl CountersDispatcher {
+    pub fn new(
+        receiver: watch::Receiver<Counters>,
+        state: Arc<Mutex<CountersState>>,
+        proxy: EventLoopProxy<BotfaceEvent>,
+    ) -> Self {
+        Self {
+            receiver,
+            state,
+            proxy,
+        }
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            let counters = self.receiver.borrow().clone();
+            match self.state.lock() {
+                Ok(mut state) => {
+                    (*state).counters = counters;
+                }
+                Err(e) => eprintln!("{:?}", e),
+            }
+            if self.proxy.send_event(BotfaceEvent::Nonce).is_err() {
+                // event loop is gone, nothing left to update
+                break;
+            }
+            if self.receiver.changed().await.is_err() {
+                break;
+            }
+        }
+    }
+}
@@ -8,9 +8,14 @@ use glutin::event_loop::EventLoop;
 use glutin::event_loop::EventLoopProxy;
 use tokio::sync::mpsc;
 
+use crate::config::{UiConfig, WindowConfig};
 use crate::egui_ui::Chatbox;
 use crate::egui_ui::ChatboxState;
+use crate::egui_ui::ConnectionPanel;
+use crate::egui_ui::CountersPanel;
+use crate::egui_ui::CountersState;
 use crate::error::Result;
+use crate::irc::ConnectionState;
 use crate::ndi::NDIFrameData;
 
 pub enum BotfaceEvent {
@@ -19,19 +24,30 @@ pub enum BotfaceEvent {
 
 pub struct Botface {
     chatbox: Chatbox,
+    counters: CountersPanel,
+    connection: ConnectionPanel,
     event_loop: EventLoop<BotfaceEvent>,
-    frame_sender: mpsc::UnboundedSender<NDIFrameData>,
+    // chat box frames are only captured for NDI when there's someone to send them
+    frame_sender: Option<mpsc::UnboundedSender<NDIFrameData>>,
+    config: UiConfig,
 }
 
 impl Botface {
-    pub fn new(frame_sender: mpsc::UnboundedSender<NDIFrameData>) -> Result<Self> {
+    pub fn new(frame_sender: Option<mpsc::UnboundedSender<NDIFrameData>>, config: UiConfig) -> Result<Self> {
         let event_loop = glutin::event_loop::EventLoop::<BotfaceEvent>::with_user_event();
         let chatbox_state = Arc::new(Mutex::new(ChatboxState::new()));
         let chatbox = Chatbox::new(chatbox_state);
+        let counters_state = Arc::new(Mutex::new(CountersState::new()));
+        let counters = CountersPanel::new(counters_state);
+        let connection_state = Arc::new(Mutex::new(ConnectionState::default()));
+        let connection = ConnectionPanel::new(connection_state);
         Ok(Self {
             chatbox,
+            counters,
+            connection,
             event_loop,
             frame_sender,
+            config,
         })
     }
 
@@ -39,17 +55,33 @@ impl Botface {
         self.chatbox.state()
     }
 
+    pub fn counters_state(&self) -> Arc<Mutex<CountersState>> {
+        self.counters.state()
+    }
+
+    pub fn connection_state(&self) -> Arc<Mutex<ConnectionState>> {
+        self.connection.state()
+    }
+
     pub fn event_loop_proxy(&self) -> EventLoopProxy<BotfaceEvent> {
         self.event_loop.create_proxy()
     }
 
     pub fn run_event_loop(self) -> Result<()> {
-        run_event_loop(self.event_loop, self.chatbox, self.frame_sender)
+        run_event_loop(
+            self.event_loop,
+            self.chatbox,
+            self.counters,
+            self.connection,
+            self.frame_sender,
+            self.config,
+        )
     }
 }
 
 fn create_display(
     event_loop: &glutin::event_loop::EventLoop<BotfaceEvent>,
+    window: WindowConfig,
 ) -> (
     glutin::WindowedContext<glutin::PossiblyCurrent>,
     glow::Context,
@@ -57,8 +89,8 @@ fn create_display(
     let window_builder = glutin::window::WindowBuilder::new()
         .with_resizable(false)
         .with_inner_size(glutin::dpi::LogicalSize {
-            width: 800.0,
-            height: 600.0,
+            width: window.width,
+            height: window.height,
         })
         .with_title("twitchy mcbotface");
 
@@ -87,40 +119,58 @@ fn create_display(
 pub fn run_event_loop(
     event_loop: glutin::event_loop::EventLoop<BotfaceEvent>,
     mut chatbox: Chatbox,
-    frame_sender: mpsc::UnboundedSender<NDIFrameData>,
+    counters: CountersPanel,
+    connection: ConnectionPanel,
+    frame_sender: Option<mpsc::UnboundedSender<NDIFrameData>>,
+    config: UiConfig,
 ) -> Result<()> {
     // egui/glow stuff
     let mut clear_color = [0.1, 0.1, 0.1];
 
-    let (gl_window, gl) = create_display(&event_loop);
+    let (gl_window, gl) = create_display(&event_loop, config.window);
     let rc_gl = Arc::new(gl);
 
     let mut egui_glow = egui_glow::EguiGlow::new(&event_loop, rc_gl.clone());
 
+    // configured fonts go first, in order, falling back to the built in FiraCode and then to
+    // egui's own
     let mut font_definitions = FontDefinitions::default();
+    let mut names = Vec::new();
+    for path in config.fonts.iter() {
+        let name = path.display().to_string();
+        font_definitions
+            .font_data
+            .insert(name.clone(), FontData::from_owned(std::fs::read(path)?));
+        names.push(name);
+    }
     font_definitions.font_data.insert(
-        "hack-regular".to_owned(),
+        "firacode-regular".to_owned(),
         FontData::from_static(include_bytes!(
-            //"/usr/share/fonts/truetype/hack/Hack-Regular.ttf"
-            //"/usr/share/fonts/fonts-go/Go-Mono.ttf"
-            //"/usr/share/fonts/truetype/monoid/Monoid-Regular.ttf"
             "/usr/share/fonts/truetype/firacode/FiraCode-Regular.ttf"
         )),
     );
-    font_definitions
+    names.push("firacode-regular".to_owned());
+    let monospace = font_definitions
         .families
         .get_mut(&FontFamily::Monospace)
-        .unwrap()
-        .insert(0, "hack-regular".to_owned());
+        .unwrap();
+    for (index, name) in names.into_iter().enumerate() {
+        monospace.insert(index, name);
+    }
     egui_glow.egui_ctx.set_fonts(font_definitions);
-    let image =
-        image::io::Reader::open("/home/wayne/visual/photos/darktable_exported/DSC04897.jpg")?
-            .decode()?;
-    let size = [image.width() as _, image.height() as _];
-    let image_buffer = image.to_rgba8();
-    let pixels = image_buffer.as_flat_samples();
-    let color_image = egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
-    let texture: egui::TextureHandle = egui_glow.egui_ctx.load_texture("my-image", color_image, TextureFilter::Linear);
+
+    let texture = match &config.background_image {
+        Some(path) => {
+            let image = image::io::Reader::open(path)?.decode()?;
+            let size = [image.width() as _, image.height() as _];
+            let image_buffer = image.to_rgba8();
+            let pixels = image_buffer.as_flat_samples();
+            let color_image = egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
+            let texture: egui::TextureHandle = egui_glow.egui_ctx.load_texture("background-image", color_image, TextureFilter::Linear);
+            Some(texture)
+        }
+        None => None,
+    };
 
     event_loop.run(
         move |event, _, control_flow: &mut glutin::event_loop::ControlFlow| {
@@ -130,17 +180,24 @@ pub fn run_event_loop(
                 let repaint_after = egui_glow.run(gl_window.window(), |egui_ctx| {
                     egui::SidePanel::left("my_side_panel").show(egui_ctx, |ui| {
                         ui.heading("Hello World!");
+                        connection.show(ui);
                         if ui.button("Quit").clicked() {
                             quit = true;
                         }
                         ui.color_edit_button_rgb(&mut clear_color);
                     });
                     egui::CentralPanel::default().show(egui_ctx, |ui| {
-                        ui.image(&texture, texture.size_vec2());
+                        if let Some(texture) = &texture {
+                            ui.image(texture, texture.size_vec2());
+                        }
                     });
                     let chatbox_context = egui_ctx.clone();
                     let window = egui::Window::new("chat box");
                     window.show(egui_ctx, |ui| {
+                        // counters live in the chat box window so they're included in the NDI feed
+                        egui::TopBottomPanel::top("counters").show_inside(ui, |top_ui| {
+                            counters.show(top_ui);
+                        });
                         chatbox.show(ui, chatbox_context);
                     });
                 });
@@ -171,13 +228,15 @@ pub fn run_event_loop(
 
                     // draw things on top of egui here
 
-                    if let Some(state) = egui_glow
-                        .egui_ctx
-                        .memory()
-                        .areas
-                        .get(Id::new("chat box"))
-                        .cloned()
-                    {
+                    if let (Some(frame_sender), Some(state)) = (
+                        &frame_sender,
+                        egui_glow
+                            .egui_ctx
+                            .memory()
+                            .areas
+                            .get(Id::new("chat box"))
+                            .cloned(),
+                    ) {
                         // get window size
                         let window_size = gl_window.window().inner_size();
                         //println!("state: {:?}", state);
@@ -3,6 +3,15 @@ pub use chatbox::Chatbox;
 pub use chatbox::ChatboxState;
 pub use chatbox::ChatboxDispatcher;
 
+mod connection;
+pub use connection::ConnectionDispatcher;
+pub use connection::ConnectionPanel;
+
+mod counters;
+pub use counters::CountersDispatcher;
+pub use counters::CountersPanel;
+pub use counters::CountersState;
+
 mod event_loop;
 pub use event_loop::Botface;
 pub use event_loop::BotfaceEvent;
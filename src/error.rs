@@ -13,16 +13,24 @@ pub enum Error {
     InvalidSDIByteBufferCapacity,
     #[error("type conversion failed")]
     TypeConversionError(#[from] std::num::TryFromIntError),
-    #[error("failed to deserialize file")]
+    #[error("failed to deserialize file: {0}")]
     SerdeError(#[from] serde_yaml::Error),
+    #[error("invalid command definition: {0}")]
+    InvalidCommandDefinition(String),
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(String),
     #[error("failed to open file")]
     IOError(#[from] std::io::Error),
     #[error("failed to initialize twitch irc client")]
     TwitchIRCError(#[from] twitch_irc::validate::Error),
     #[error("failed to join tokio task")]
     TokioJoinError(#[from] tokio::task::JoinError),
-    #[error("failed to send twitch irc message")]
-    TwitchIRCMessageSendError(#[from] twitch_irc::Error<twitch_irc::SecureTCPTransport, twitch_irc::login::StaticLoginCredentials>),
+    #[error("failed to send twitch irc message: {0}")]
+    TwitchIRCMessageSendError(String),
+    #[error("twitch rejected the login: {0}")]
+    TwitchLoginError(String),
+    #[error("invalid irc message: {0}")]
+    IRCParseError(#[from] twitch_irc::message::IRCParseError),
     #[error("something bad: {0}")]
     SomethingBad(String),
 
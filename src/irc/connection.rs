@@ -0,0 +1,79 @@
+use std::fmt;
+use std::time::Duration;
+
+// delay before the first reconnect attempt, doubled for each attempt after it
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// How long the connection may stay silent before it's considered dead. The client pings Twitch
+/// every 30 seconds, so a healthy connection is never quiet for this long.
+pub const SILENCE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// State of the connection to Twitch, published by `IrcCore` as an `IrcEvent::Connection`
+/// whenever it changes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Connecting and logging in for the first time.
+    Connecting,
+    /// Logged in, channels can be joined.
+    Connected,
+    /// Connecting again after the connection was lost or Twitch asked for a reconnect.
+    Reconnecting { attempt: u32 },
+    /// Not connected, and why.
+    Disconnected(String),
+}
+
+impl ConnectionState {
+    pub fn is_connected(&self) -> bool {
+        matches!(self, ConnectionState::Connected)
+    }
+}
+
+impl Default for ConnectionState {
+    fn default() -> Self {
+        ConnectionState::Disconnected("not connected yet".to_string())
+    }
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionState::Connecting => write!(f, "connecting"),
+            ConnectionState::Connected => write!(f, "connected"),
+            ConnectionState::Reconnecting { attempt } => {
+                write!(f, "reconnecting (attempt {})", attempt)
+            }
+            ConnectionState::Disconnected(reason) => write!(f, "disconnected: {}", reason),
+        }
+    }
+}
+
+/// Delay before reconnect attempt `attempt`, counting from 1, backing off exponentially. Attempt
+/// 0 waits as long as the first.
+pub fn reconnect_delay(attempt: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+    RECONNECT_DELAY
+        .saturating_mul(factor)
+        .min(MAX_RECONNECT_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconnect_delay_doubles_up_to_the_maximum() {
+        assert_eq!(reconnect_delay(1), Duration::from_secs(1));
+        assert_eq!(reconnect_delay(2), Duration::from_secs(2));
+        assert_eq!(reconnect_delay(3), Duration::from_secs(4));
+        assert_eq!(reconnect_delay(6), Duration::from_secs(32));
+        assert_eq!(reconnect_delay(7), MAX_RECONNECT_DELAY);
+        assert_eq!(reconnect_delay(u32::MAX), MAX_RECONNECT_DELAY);
+    }
+
+    #[test]
+    fn the_first_attempt_waits_the_initial_delay() {
+        assert_eq!(reconnect_delay(1), RECONNECT_DELAY);
+        assert_eq!(reconnect_delay(0), RECONNECT_DELAY);
+    }
+}
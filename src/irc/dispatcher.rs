@@ -0,0 +1,343 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+use tokio::sync::{broadcast, mpsc};
+use twitch_irc::message::ServerMessage;
+
+use crate::irc::{ComponentMessage, ConnectionState};
+
+// number of messages buffered on the command and event paths
+const COMMAND_CAPACITY: usize = 200;
+const EVENT_CAPACITY: usize = 200;
+
+/// Sends commands to `IrcCore`, which is their only consumer.
+#[derive(Clone, Debug)]
+pub struct CommandSender {
+    sender: mpsc::Sender<ComponentMessage>,
+}
+
+impl CommandSender {
+    /// Queue `message` for `IrcCore`, waiting for room if its queue is full. Fails only if it's
+    /// gone.
+    pub async fn send(&self, message: ComponentMessage) -> Result<(), SendError<ComponentMessage>> {
+        self.sender.send(message).await
+    }
+
+    /// Queue `message` for `IrcCore` without waiting, failing if its queue is full or it's gone.
+    pub fn try_send(
+        &self,
+        message: ComponentMessage,
+    ) -> Result<(), TrySendError<ComponentMessage>> {
+        self.sender.try_send(message)
+    }
+}
+
+/// Counters kept for each event subscriber.
+#[derive(Debug, Default)]
+pub struct SubscriberMetrics {
+    received: AtomicU64,
+    lagged: AtomicU64,
+    skipped: AtomicU64,
+}
+
+/// Snapshot of a subscriber's `SubscriberMetrics`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubscriberStats {
+    pub name: String,
+    /// Events received.
+    pub received: u64,
+    /// Times the subscriber fell behind the event path.
+    pub lagged: u64,
+    /// Events missed by falling behind.
+    pub skipped: u64,
+}
+
+/// Event published to every subscriber.
+// nearly every event is a message, boxing them would only add an allocation to each
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug)]
+pub enum IrcEvent {
+    /// Message from the server.
+    Message(ServerMessage),
+    /// The connection to the server changed state.
+    Connection(ConnectionState),
+}
+
+type MetricsRegistry = Arc<Mutex<Vec<(String, Weak<SubscriberMetrics>)>>>;
+
+/// The shared end of a bus, which `IrcCore` publishes events to and components subscribe to.
+/// Unlike a `MessageDispatcher` it receives no events itself, so publishing to it doesn't make
+/// it a subscriber. The bus closes once every clone of it is dropped.
+#[derive(Clone)]
+pub struct EventBus {
+    commands: CommandSender,
+    events: Arc<broadcast::Sender<IrcEvent>>,
+    // latest connection state, for subscribers that missed the event
+    connection: Arc<Mutex<ConnectionState>>,
+    registry: MetricsRegistry,
+}
+
+impl EventBus {
+    /// Create a new bus along with the receiving end of its command path.
+    pub(crate) fn new() -> (Self, mpsc::Receiver<ComponentMessage>) {
+        let (command_sender, command_receiver) = mpsc::channel(COMMAND_CAPACITY);
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let bus = Self {
+            commands: CommandSender {
+                sender: command_sender,
+            },
+            events: Arc::new(events),
+            connection: Arc::default(),
+            registry: MetricsRegistry::default(),
+        };
+        (bus, command_receiver)
+    }
+
+    /// A new subscriber named `name`, receiving events published from now on.
+    pub fn subscriber(&self, name: &str) -> MessageDispatcher {
+        MessageDispatcher {
+            name: name.to_string(),
+            commands: self.commands.clone(),
+            events: Arc::downgrade(&self.events),
+            connection: self.connection.clone(),
+            registry: self.registry.clone(),
+            receiver: self.events.subscribe(),
+            metrics: register(&self.registry, name),
+        }
+    }
+
+    /// Publish an event to every subscriber. Returns the number of subscribers it was sent to.
+    pub(crate) fn publish(&self, message: ServerMessage) -> usize {
+        self.publish_event(IrcEvent::Message(message))
+    }
+
+    /// The current state of the connection to the server.
+    pub fn connection_state(&self) -> ConnectionState {
+        lock_connection(&self.connection).clone()
+    }
+
+    /// Record the connection's new `state` and publish it, unless it's unchanged. Returns whether
+    /// it changed.
+    pub(crate) fn publish_connection(&self, state: ConnectionState) -> bool {
+        {
+            let mut connection = lock_connection(&self.connection);
+            if *connection == state {
+                return false;
+            }
+            *connection = state.clone();
+        }
+        self.publish_event(IrcEvent::Connection(state));
+        true
+    }
+
+    fn publish_event(&self, event: IrcEvent) -> usize {
+        // the only error is there being no subscribers at all
+        self.events.send(event).unwrap_or(0)
+    }
+
+    /// Metrics of every live subscriber on this bus.
+    pub fn metrics(&self) -> Vec<SubscriberStats> {
+        metrics(&self.registry)
+    }
+}
+
+/// A component's connection to `IrcCore`: commands go to the core over an MPSC path, while every
+/// subscriber receives its own copy of each event from the server.
+///
+/// A subscriber that falls behind skips the events it missed and carries on, rather than
+/// disconnecting; see `recv`.
+pub struct MessageDispatcher {
+    name: String,
+    commands: CommandSender,
+    // subscribers don't keep the bus open, so they see it close once `IrcCore` is gone
+    events: Weak<broadcast::Sender<IrcEvent>>,
+    connection: Arc<Mutex<ConnectionState>>,
+    registry: MetricsRegistry,
+    receiver: broadcast::Receiver<IrcEvent>,
+    metrics: Arc<SubscriberMetrics>,
+}
+
+impl MessageDispatcher {
+    /// A new subscriber to the same bus, receiving events published from now on, or none at all
+    /// if the bus is closed.
+    pub fn subscriber(&self, name: &str) -> Self {
+        let receiver = match self.events.upgrade() {
+            Some(events) => events.subscribe(),
+            // a receiver whose sender is gone, so it's closed too
+            None => broadcast::channel(1).1,
+        };
+        Self {
+            name: name.to_string(),
+            commands: self.commands.clone(),
+            events: self.events.clone(),
+            connection: self.connection.clone(),
+            registry: self.registry.clone(),
+            receiver,
+            metrics: register(&self.registry, name),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Send a command to `IrcCore`, waiting for room in its queue, see `CommandSender::send`.
+    pub async fn send(&self, message: ComponentMessage) -> Result<(), SendError<ComponentMessage>> {
+        self.commands.send(message).await
+    }
+
+    /// Send a command to `IrcCore` without waiting, see `CommandSender::try_send`.
+    pub fn try_send(
+        &self,
+        message: ComponentMessage,
+    ) -> Result<(), TrySendError<ComponentMessage>> {
+        self.commands.try_send(message)
+    }
+
+    /// A sender for commands that can be moved into other tasks.
+    pub fn command_sender(&self) -> CommandSender {
+        self.commands.clone()
+    }
+
+    /// Receive the next event, or `None` once the bus is closed, ie the `IrcCore` and every other
+    /// `EventBus` are gone. Events missed by falling behind are skipped, logged and counted in
+    /// this subscriber's metrics.
+    pub async fn recv(&mut self) -> Option<IrcEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(message) => {
+                    self.metrics.received.fetch_add(1, Ordering::Relaxed);
+                    return Some(message);
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    self.metrics.lagged.fetch_add(1, Ordering::Relaxed);
+                    self.metrics.skipped.fetch_add(skipped, Ordering::Relaxed);
+                    println!("{} fell behind, skipped {} events", self.name, skipped);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// The current state of the connection to the server.
+    pub fn connection_state(&self) -> ConnectionState {
+        lock_connection(&self.connection).clone()
+    }
+
+    /// Metrics of every live subscriber on this bus.
+    pub fn metrics(&self) -> Vec<SubscriberStats> {
+        metrics(&self.registry)
+    }
+}
+
+impl Clone for MessageDispatcher {
+    /// A new subscriber with the same name, see `subscriber`.
+    fn clone(&self) -> Self {
+        self.subscriber(&self.name)
+    }
+}
+
+fn metrics(registry: &MetricsRegistry) -> Vec<SubscriberStats> {
+    let mut registry = lock_registry(registry);
+    registry.retain(|(_, metrics)| metrics.strong_count() > 0);
+    registry
+        .iter()
+        .filter_map(|(name, metrics)| {
+            let metrics = metrics.upgrade()?;
+            Some(SubscriberStats {
+                name: name.clone(),
+                received: metrics.received.load(Ordering::Relaxed),
+                lagged: metrics.lagged.load(Ordering::Relaxed),
+                skipped: metrics.skipped.load(Ordering::Relaxed),
+            })
+        })
+        .collect()
+}
+
+fn register(registry: &MetricsRegistry, name: &str) -> Arc<SubscriberMetrics> {
+    let metrics = Arc::new(SubscriberMetrics::default());
+    lock_registry(registry).push((name.to_string(), Arc::downgrade(&metrics)));
+    metrics
+}
+
+fn lock_connection(
+    connection: &Mutex<ConnectionState>,
+) -> std::sync::MutexGuard<'_, ConnectionState> {
+    connection.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn lock_registry(
+    registry: &MetricsRegistry,
+) -> std::sync::MutexGuard<'_, Vec<(String, Weak<SubscriberMetrics>)>> {
+    registry.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twitch_irc::message::IRCMessage;
+
+    fn ping() -> ServerMessage {
+        ServerMessage::try_from(IRCMessage::parse("PING :tmi.twitch.tv").unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn publishing_reaches_only_subscribers() {
+        let (bus, _commands) = EventBus::new();
+        assert_eq!(bus.publish(ping()), 0);
+
+        let mut subscriber = bus.subscriber("test");
+        assert_eq!(bus.publish(ping()), 1);
+        assert!(matches!(
+            subscriber.recv().await,
+            Some(IrcEvent::Message(ServerMessage::Ping(_)))
+        ));
+
+        let stats = bus.metrics();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].name, "test");
+        assert_eq!(stats[0].received, 1);
+
+        drop(subscriber);
+        assert!(bus.metrics().is_empty());
+    }
+
+    #[tokio::test]
+    async fn subscribers_see_the_bus_close() {
+        let (bus, _commands) = EventBus::new();
+        let mut subscriber = bus.subscriber("test");
+        bus.publish(ping());
+        drop(bus);
+        // events published before the bus closed are still received
+        assert!(subscriber.recv().await.is_some());
+        assert!(subscriber.recv().await.is_none());
+        assert!(subscriber.subscriber("late").recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn send_waits_for_room() {
+        let (bus, mut commands) = EventBus::new();
+        let dispatcher = bus.subscriber("test");
+        let message = || {
+            ComponentMessage::JoinChannel(crate::irc::JoinChannelMessage {
+                channel: "uuayn".to_string(),
+            })
+        };
+        for _ in 0..COMMAND_CAPACITY {
+            dispatcher.try_send(message()).unwrap();
+        }
+        assert!(matches!(
+            dispatcher.try_send(message()),
+            Err(TrySendError::Full(_))
+        ));
+
+        let sender = dispatcher.command_sender();
+        let mut waiting = tokio::spawn(async move { sender.send(message()).await });
+        let wait = std::time::Duration::from_millis(50);
+        assert!(tokio::time::timeout(wait, &mut waiting).await.is_err());
+        commands.recv().await.unwrap();
+        waiting.await.unwrap().unwrap();
+    }
+}
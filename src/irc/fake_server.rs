@@ -0,0 +1,348 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use futures::channel::mpsc as futures_mpsc;
+use futures::stream::StreamExt;
+use tokio::sync::mpsc;
+use twitch_irc::message::{AsRawIRC, IRCMessage, PrivmsgMessage};
+
+use crate::error::{Error, Result};
+use crate::irc::memory::{self, ServerConnection};
+use crate::irc::{ConnectionState, IrcEvent, MessageDispatcher};
+
+/// How long `expect` waits for the bot to send a matching message.
+pub const EXPECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Scriptable stand-in for Twitch's IRC server, for testing the bot end to end without a
+/// network. The bot reaches it through `InMemoryTransport`, eg with
+/// `IrcCore::<InMemoryTransport>::with_transport()`, from the time it's started until another
+/// one is started on the same thread.
+///
+/// Like Twitch, it acknowledges logins, joins, parts, pings and chat messages by itself, logins
+/// as set with `set_login_response`. The rest is scripted: lines given to `send`, eg tagged
+/// PRIVMSG and USERNOTICE lines captured from Twitch, are replayed to the bot, and `expect` waits
+/// for the bot to send a message. A script for `run_script` does both, a step per line:
+///
+/// ```text
+/// # blank lines and comments are skipped
+/// < JOIN #uuayn
+/// > @badge-info=;badges=;color=;display-name=Viewer;emotes=;first-msg=0;flags=;id=1;mod=0;room-id=1;subscriber=0;tmi-sent-ts=1;turbo=0;user-id=2;user-type= :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #uuayn :!meow
+/// < PRIVMSG #uuayn :. woof
+/// ```
+///
+/// `>` lines are sent to the bot and `<` lines are expected from it, see `expect`. Messages the
+/// bot sends from different tasks, eg a reply and the JOIN it announces, may arrive in either
+/// order; `expect_all` waits for them without relying on one.
+pub struct FakeTwitchServer {
+    sessions: Arc<Mutex<Vec<Arc<Session>>>>,
+    login_response: Arc<Mutex<LoginResponse>>,
+    // everything the bot sent, in order
+    sent: mpsc::UnboundedReceiver<IRCMessage>,
+}
+
+/// How `FakeTwitchServer` answers the bot logging in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoginResponse {
+    /// Welcome the bot, like Twitch does for valid credentials.
+    Accept,
+    /// Send the NOTICE Twitch sends for invalid credentials.
+    Reject,
+    /// Say nothing, like a server that stopped responding.
+    Ignore,
+}
+
+// a connection from the bot
+struct Session {
+    login: Mutex<String>,
+    login_response: Arc<Mutex<LoginResponse>>,
+    channels: Mutex<HashSet<String>>,
+    outgoing: futures_mpsc::UnboundedSender<IRCMessage>,
+}
+
+impl FakeTwitchServer {
+    /// Start listening for `InMemoryTransport` connections made on this thread. Must be called
+    /// from within a current-thread tokio runtime, which the bot must run on too.
+    pub fn start() -> Self {
+        let (connector, mut connections) = mpsc::unbounded_channel::<ServerConnection>();
+        memory::listen(connector);
+
+        let (sent_sender, sent) = mpsc::unbounded_channel();
+        let sessions = Arc::new(Mutex::new(Vec::new()));
+        let login_response = Arc::new(Mutex::new(LoginResponse::Accept));
+        let accepted = sessions.clone();
+        let session_login_response = login_response.clone();
+        tokio::spawn(async move {
+            while let Some(connection) = connections.recv().await {
+                let session = Arc::new(Session {
+                    login: Mutex::new(String::new()),
+                    login_response: session_login_response.clone(),
+                    channels: Mutex::new(HashSet::new()),
+                    outgoing: connection.outgoing,
+                });
+                lock(&accepted).push(session.clone());
+                tokio::spawn(session.serve(connection.incoming, sent_sender.clone()));
+            }
+        });
+
+        Self {
+            sessions,
+            login_response,
+            sent,
+        }
+    }
+
+    /// Answer logins from now on with `response`, `LoginResponse::Accept` to begin with.
+    pub fn set_login_response(&self, response: LoginResponse) {
+        *lock(&self.login_response) = response;
+    }
+
+    /// Send the raw IRC `line` to the bot. Like on Twitch, a message to a channel only reaches
+    /// connections that joined it, and it's an error if there's none.
+    pub fn send(&self, line: &str) -> Result<()> {
+        let message = IRCMessage::parse(line)?;
+        let channel = message
+            .params
+            .first()
+            .and_then(|param| param.strip_prefix('#'))
+            .map(str::to_string);
+
+        let mut sessions = lock(&self.sessions);
+        sessions.retain(|session| !session.outgoing.is_closed());
+        let mut delivered = false;
+        for session in sessions.iter() {
+            let joined = match &channel {
+                Some(channel) => lock(&session.channels).contains(channel),
+                None => true,
+            };
+            if joined && session.outgoing.unbounded_send(message.clone()).is_ok() {
+                delivered = true;
+            }
+        }
+        if delivered {
+            Ok(())
+        } else {
+            Err(Error::SomethingBad(format!(
+                "no connection to send {} to",
+                line
+            )))
+        }
+    }
+
+    /// Wait for the bot to send a message containing `pattern` in its raw IRC form, skipping
+    /// any others, for up to `EXPECT_TIMEOUT`. Messages sent with `say` are prefixed with ". ".
+    pub async fn expect(&mut self, pattern: &str) -> Result<IRCMessage> {
+        let sent = &mut self.sent;
+        let found = async {
+            while let Some(message) = sent.recv().await {
+                if message.as_raw_irc().contains(pattern) {
+                    return Some(message);
+                }
+            }
+            None
+        };
+        match tokio::time::timeout(EXPECT_TIMEOUT, found).await {
+            Ok(Some(message)) => Ok(message),
+            Ok(None) => Err(Error::SomethingBad(
+                "fake Twitch server stopped".to_string(),
+            )),
+            Err(_) => Err(Error::SomethingBad(format!(
+                "bot didn't send \"{}\" within {}s",
+                pattern,
+                EXPECT_TIMEOUT.as_secs()
+            ))),
+        }
+    }
+
+    /// Wait for the bot to send a message containing each of `patterns`, in any order, skipping
+    /// any others, for up to `EXPECT_TIMEOUT` in all. See `expect`.
+    pub async fn expect_all(&mut self, patterns: &[&str]) -> Result<()> {
+        let mut missing = patterns.to_vec();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let sent = &mut self.sent;
+        let found = async {
+            while let Some(message) = sent.recv().await {
+                let raw = message.as_raw_irc();
+                if let Some(index) = missing.iter().position(|pattern| raw.contains(pattern)) {
+                    missing.remove(index);
+                    if missing.is_empty() {
+                        return true;
+                    }
+                }
+            }
+            false
+        };
+        match tokio::time::timeout(EXPECT_TIMEOUT, found).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(Error::SomethingBad(
+                "fake Twitch server stopped".to_string(),
+            )),
+            Err(_) => Err(Error::SomethingBad(format!(
+                "bot didn't send \"{}\" within {}s",
+                missing.join("\", \""),
+                EXPECT_TIMEOUT.as_secs()
+            ))),
+        }
+    }
+
+    /// Run `script`, failing at the first line that can't be sent or whose message the bot
+    /// doesn't send.
+    pub async fn run_script(&mut self, script: &str) -> Result<()> {
+        for (index, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = if let Some(raw) = line.strip_prefix('>') {
+                self.send(raw.trim())
+            } else if let Some(pattern) = line.strip_prefix('<') {
+                self.expect(pattern.trim()).await.map(|_| ())
+            } else {
+                Err(Error::SomethingBad(
+                    "expected a line starting with > or <".to_string(),
+                ))
+            };
+            result.map_err(|e| Error::SomethingBad(format!("script line {}: {}", index + 1, e)))?;
+        }
+        Ok(())
+    }
+}
+
+impl Session {
+    async fn serve(
+        self: Arc<Self>,
+        mut incoming: futures_mpsc::UnboundedReceiver<IRCMessage>,
+        sent: mpsc::UnboundedSender<IRCMessage>,
+    ) {
+        while let Some(message) = incoming.next().await {
+            // respond first, so a test seeing the message can rely on its effects
+            self.respond(&message);
+            // the server may have been dropped, leaving nobody to look at it
+            let _ = sent.send(message);
+        }
+    }
+
+    fn respond(&self, message: &IRCMessage) {
+        let param = |index: usize| message.params.get(index).cloned().unwrap_or_default();
+        match message.command.as_str() {
+            "CAP" => self.reply(&format!(":tmi.twitch.tv CAP * ACK :{}", param(1))),
+            "NICK" => {
+                let login = param(0);
+                *lock(&self.login) = login.clone();
+                let response = *lock(&self.login_response);
+                match response {
+                    LoginResponse::Accept => {
+                        self.reply(&format!(":tmi.twitch.tv 001 {} :Welcome, GLHF!", login));
+                        self.reply(&format!(
+                            "@badge-info=;badges=;color=;display-name={};emote-sets=0;user-id=1;user-type= :tmi.twitch.tv GLOBALUSERSTATE",
+                            login
+                        ));
+                    }
+                    LoginResponse::Reject => {
+                        self.reply(":tmi.twitch.tv NOTICE * :Login authentication failed")
+                    }
+                    LoginResponse::Ignore => (),
+                }
+            }
+            "JOIN" => {
+                let login = lock(&self.login).clone();
+                for channel in param(0).split(',').filter_map(|c| c.strip_prefix('#')) {
+                    lock(&self.channels).insert(channel.to_string());
+                    self.reply(&format!(
+                        ":{0}!{0}@{0}.tmi.twitch.tv JOIN #{1}",
+                        login, channel
+                    ));
+                    self.reply(&format!(
+                        "@emote-only=0;followers-only=-1;r9k=0;room-id=1;slow=0;subs-only=0 :tmi.twitch.tv ROOMSTATE #{}",
+                        channel
+                    ));
+                    self.reply(&user_state(&login, channel));
+                }
+            }
+            "PART" => {
+                let login = lock(&self.login).clone();
+                for channel in param(0).split(',').filter_map(|c| c.strip_prefix('#')) {
+                    lock(&self.channels).remove(channel);
+                    self.reply(&format!(
+                        ":{0}!{0}@{0}.tmi.twitch.tv PART #{1}",
+                        login, channel
+                    ));
+                }
+            }
+            "PING" => self.reply(&format!(":tmi.twitch.tv PONG tmi.twitch.tv :{}", param(0))),
+            // Twitch acknowledges every chat message with a USERSTATE
+            "PRIVMSG" => {
+                if let Some(channel) = param(0).strip_prefix('#') {
+                    self.reply(&user_state(&lock(&self.login), channel));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn reply(&self, line: &str) {
+        match IRCMessage::parse(line) {
+            Ok(message) => {
+                // the bot closed the connection, nothing left to reply to
+                let _ = self.outgoing.unbounded_send(message);
+            }
+            Err(e) => println!("fake Twitch server failed to reply {}: {}", line, e),
+        }
+    }
+}
+
+/// A raw PRIVMSG of `text` in `channel` sent by `login`, tagged like Twitch's, for `send`.
+/// `login` is the broadcaster if it's their channel, otherwise a viewer without badges.
+pub fn privmsg_line(login: &str, channel: &str, text: &str) -> String {
+    let badges = if login == channel {
+        "broadcaster/1"
+    } else {
+        ""
+    };
+    format!(
+        "@badge-info=;badges={1};color=;display-name={0};emotes=;first-msg=0;flags=;id=1;mod=0;room-id=1;subscriber=0;tmi-sent-ts=1;turbo=0;user-id=2;user-type= :{0}!{0}@{0}.tmi.twitch.tv PRIVMSG #{2} :{3}",
+        login, badges, channel, text
+    )
+}
+
+/// The PRIVMSG of `privmsg_line`, parsed, eg for testing commanders. Panics if `text` doesn't
+/// fit in a single IRC line.
+pub fn privmsg(login: &str, channel: &str, text: &str) -> PrivmsgMessage {
+    let message = IRCMessage::parse(&privmsg_line(login, channel, text)).unwrap();
+    PrivmsgMessage::try_from(message).unwrap()
+}
+
+/// Wait for the next connection state published on `events`, skipping other events, for up to
+/// `EXPECT_TIMEOUT`.
+pub async fn next_connection_state(events: &mut MessageDispatcher) -> Result<ConnectionState> {
+    let next = async {
+        loop {
+            match events.recv().await {
+                Some(IrcEvent::Connection(state)) => return Some(state),
+                Some(IrcEvent::Message(_)) => continue,
+                None => return None,
+            }
+        }
+    };
+    match tokio::time::timeout(EXPECT_TIMEOUT, next).await {
+        Ok(Some(state)) => Ok(state),
+        Ok(None) => Err(Error::SomethingBad("the event bus closed".to_string())),
+        Err(_) => Err(Error::SomethingBad(format!(
+            "no connection state within {}s",
+            EXPECT_TIMEOUT.as_secs()
+        ))),
+    }
+}
+
+fn user_state(login: &str, channel: &str) -> String {
+    format!(
+        "@badge-info=;badges=;color=;display-name={};emote-sets=0;mod=0;subscriber=0;user-type= :tmi.twitch.tv USERSTATE #{}",
+        login, channel
+    )
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
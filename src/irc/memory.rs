@@ -0,0 +1,93 @@
+use std::cell::RefCell;
+use std::convert::Infallible;
+use std::fmt;
+
+use async_trait::async_trait;
+use futures::channel::mpsc as futures_mpsc;
+use futures::sink::Sink;
+use futures::stream::{FusedStream, StreamExt};
+use itertools::Either;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use twitch_irc::message::{IRCMessage, IRCParseError};
+use twitch_irc::transport::Transport;
+
+thread_local! {
+    // where new in-memory connections are handed to the server, see `listen`; kept per thread
+    // because `Transport::new` takes no arguments, so tests running side by side each reach
+    // their own server
+    static CONNECTOR: RefCell<Option<mpsc::UnboundedSender<ServerConnection>>> =
+        const { RefCell::new(None) };
+}
+
+/// The server's end of an `InMemoryTransport` connection.
+pub(crate) struct ServerConnection {
+    /// Messages sent by the client.
+    pub incoming: futures_mpsc::UnboundedReceiver<IRCMessage>,
+    /// Messages for the client, the connection is closed when this is dropped.
+    pub outgoing: futures_mpsc::UnboundedSender<IRCMessage>,
+}
+
+/// Make new `InMemoryTransport` connections made on this thread to `connector`, replacing any
+/// earlier listener.
+pub(crate) fn listen(connector: mpsc::UnboundedSender<ServerConnection>) {
+    CONNECTOR.with(|current| *current.borrow_mut() = Some(connector));
+}
+
+#[derive(Debug, Error)]
+#[error("no in-memory IRC server is listening on this thread")]
+pub struct NotListening;
+
+type Incoming = Box<
+    dyn FusedStream<Item = Result<IRCMessage, Either<Infallible, IRCParseError>>>
+        + Unpin
+        + Send
+        + Sync,
+>;
+type Outgoing = Box<dyn Sink<IRCMessage, Error = futures_mpsc::SendError> + Unpin + Send + Sync>;
+
+/// Connects to a server in the same process, eg a `FakeTwitchServer`, instead of to Twitch.
+/// Connections go to the server started last on the same thread, so the bot and the server must
+/// share a current-thread runtime, like that of `#[tokio::test]`; connecting fails if there's
+/// no server.
+pub struct InMemoryTransport {
+    incoming: Incoming,
+    outgoing: Outgoing,
+}
+
+#[async_trait]
+impl Transport for InMemoryTransport {
+    type ConnectError = NotListening;
+    type IncomingError = Infallible;
+    type OutgoingError = futures_mpsc::SendError;
+
+    type Incoming = Incoming;
+    type Outgoing = Outgoing;
+
+    async fn new() -> Result<Self, NotListening> {
+        let (client_sender, server_receiver) = futures_mpsc::unbounded();
+        let (server_sender, client_receiver) = futures_mpsc::unbounded();
+        let connection = ServerConnection {
+            incoming: server_receiver,
+            outgoing: server_sender,
+        };
+        let connector = CONNECTOR
+            .with(|current| current.borrow().clone())
+            .ok_or(NotListening)?;
+        connector.send(connection).map_err(|_| NotListening)?;
+        Ok(Self {
+            incoming: Box::new(client_receiver.map(Ok)),
+            outgoing: Box::new(client_sender),
+        })
+    }
+
+    fn split(self) -> (Incoming, Outgoing) {
+        (self.incoming, self.outgoing)
+    }
+}
+
+impl fmt::Debug for InMemoryTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InMemoryTransport").finish()
+    }
+}
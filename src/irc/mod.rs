@@ -0,0 +1,957 @@
+use twitch_irc::login::{LoginCredentials, StaticLoginCredentials};
+use twitch_irc::message::ServerMessage;
+use twitch_irc::transport::Transport;
+use twitch_irc::TwitchIRCClient;
+use twitch_irc::{ClientConfig, SecureTCPTransport};
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{mpsc, watch, Notify};
+use tokio::task::JoinHandle;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::error::{Error, Result};
+
+mod connection;
+pub use connection::reconnect_delay;
+pub use connection::ConnectionState;
+pub use connection::SILENCE_TIMEOUT;
+
+mod dispatcher;
+pub use dispatcher::CommandSender;
+pub use dispatcher::EventBus;
+pub use dispatcher::IrcEvent;
+pub use dispatcher::MessageDispatcher;
+pub use dispatcher::SubscriberMetrics;
+pub use dispatcher::SubscriberStats;
+
+// for testing the bot without Twitch, also for integration tests with the test-util feature
+#[cfg(any(test, feature = "test-util"))]
+mod fake_server;
+#[cfg(any(test, feature = "test-util"))]
+pub use fake_server::next_connection_state;
+#[cfg(any(test, feature = "test-util"))]
+pub use fake_server::privmsg;
+#[cfg(any(test, feature = "test-util"))]
+pub use fake_server::privmsg_line;
+#[cfg(any(test, feature = "test-util"))]
+pub use fake_server::FakeTwitchServer;
+#[cfg(any(test, feature = "test-util"))]
+pub use fake_server::LoginResponse;
+
+#[cfg(any(test, feature = "test-util"))]
+mod memory;
+#[cfg(any(test, feature = "test-util"))]
+pub use memory::InMemoryTransport;
+
+mod queue;
+pub use queue::DropPolicy;
+pub use queue::OutgoingQueue;
+pub use queue::Priority;
+pub use queue::QueuePolicy;
+
+mod rate_limit;
+pub use rate_limit::RateLimiter;
+pub use rate_limit::TokenBucket;
+pub use rate_limit::Traffic;
+
+/// Maximum length of a chat message accepted by Twitch, in characters.
+pub const MAX_MESSAGE_LENGTH: usize = 500;
+
+// `TwitchIRCClient::say` prefixes messages with ". " so they aren't executed as commands
+const SAY_OVERHEAD: usize = 2;
+
+// channel whispers are sent from
+const WHISPER_CHANNEL: &str = "jtv";
+
+/// How long `IrcCore` waits to log in, on connecting and after Twitch asks to reconnect, before
+/// replacing the connection.
+pub const LOGIN_TIMEOUT: Duration = Duration::from_secs(30);
+// how often `run_irc` logs the metrics of each subscriber
+const METRICS_LOG_INTERVAL: Duration = Duration::from_secs(10 * 60);
+// how long `say_once` waits for Twitch to acknowledge joining and each message sent
+const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Passes messages between Twitch and the components, connecting with transport `T` and logging
+/// in with credentials `L`.
+pub struct IrcCore<T = SecureTCPTransport, L = StaticLoginCredentials> {
+    bus: EventBus,
+    // taken by `run_irc`
+    command_receiver: Option<mpsc::Receiver<ComponentMessage>>,
+    rate_limiter: Arc<Mutex<RateLimiter>>,
+    queue_policy: QueuePolicy,
+    login_timeout: Duration,
+    // the transport and credentials are only used by `run_irc`
+    client: PhantomData<fn() -> (T, L)>,
+}
+
+// messages waiting to be sent to a channel, and a notification for its sender task when one is
+// queued, the channel is to be joined or the queue is closed
+struct ChannelQueue {
+    messages: Mutex<OutgoingQueue>,
+    notify: Notify,
+    // set when the channel is to be joined, eg again after a reconnect, the sender task joins it
+    // before sending anything else
+    join: AtomicBool,
+    // set on leaving the channel, the sender task leaves it once the queue is empty
+    closed: AtomicBool,
+}
+
+impl ChannelQueue {
+    fn join(&self) {
+        self.join.store(true, Ordering::Relaxed);
+        self.notify.notify_one();
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.notify.notify_one();
+    }
+}
+
+// a channel the bot is in, or is about to join
+struct OpenChannel {
+    queue: Arc<ChannelQueue>,
+    sender: JoinHandle<()>,
+}
+
+impl IrcCore {
+    pub fn new() -> Self {
+        Self::with_transport()
+    }
+}
+
+impl Default for IrcCore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Transport, L: LoginCredentials + Clone> IrcCore<T, L> {
+    /// An IRC core connecting some other way than over TLS, eg
+    /// `IrcCore::<PlainTCPTransport>::with_transport()`, or to a `FakeTwitchServer` with
+    /// `InMemoryTransport`.
+    pub fn with_transport() -> Self {
+        let (bus, command_receiver) = EventBus::new();
+        Self {
+            bus,
+            command_receiver: Some(command_receiver),
+            rate_limiter: Arc::new(Mutex::new(RateLimiter::new())),
+            queue_policy: QueuePolicy::default(),
+            login_timeout: LOGIN_TIMEOUT,
+            client: PhantomData,
+        }
+    }
+
+    /// Limit the outgoing message queue of each channel according to `policy`.
+    pub fn with_queue_policy(mut self, policy: QueuePolicy) -> Self {
+        self.queue_policy = policy;
+        self
+    }
+
+    /// Replace connections that haven't logged in within `timeout`, `LOGIN_TIMEOUT` by default.
+    pub fn with_login_timeout(mut self, timeout: Duration) -> Self {
+        self.login_timeout = timeout;
+        self
+    }
+
+    /// Connect to Twitch and pass messages between it and the components, publishing each change
+    /// of `ConnectionState`. A connection that goes silent or doesn't log in within the login
+    /// timeout is replaced, backing off between attempts; components keep their subscriptions
+    /// and channels are joined again on login, see `ChannelManager`.
+    ///
+    /// Only returns if Twitch rejects `login_credentials`, since connecting again won't help.
+    pub async fn run_irc(&mut self, login_credentials: L) -> Result<()> {
+        let component_message_receiver = match self.command_receiver.take() {
+            Some(receiver) => receiver,
+            None => {
+                println!("IRC core is already running");
+                return Ok(());
+            }
+        };
+
+        let (mut incoming_messages, client) = new_client::<T, L>(&login_credentials);
+        // components send through whichever client is current
+        let (client_sender, client_receiver) = watch::channel(client);
+
+        // handle messages received from components
+        let component_message_handler_rate_limiter = self.rate_limiter.clone();
+        let queue_policy = self.queue_policy;
+        let component_message_handler = tokio::spawn(async move {
+            Self::component_message_handler(
+                client_receiver,
+                component_message_receiver,
+                component_message_handler_rate_limiter,
+                queue_policy,
+            )
+            .await;
+        });
+
+        let metrics_logger = tokio::spawn(log_metrics(self.bus.clone()));
+
+        // handle messages received from IRC server by broadcasting to all components
+        let bus = &self.bus;
+        set_connection_state(bus, ConnectionState::Connecting);
+        let mut attempt = 0;
+        loop {
+            // the client only connects on demand, but components wait for the login before
+            // joining channels
+            let client = client_sender.borrow().clone();
+            client.connect().await;
+
+            let result = Self::server_message_handler(
+                &mut incoming_messages,
+                bus,
+                &self.rate_limiter,
+                self.login_timeout,
+            )
+            .await;
+            let reason = match result {
+                Ok(reason) => reason,
+                Err(e) => {
+                    set_connection_state(bus, ConnectionState::Disconnected(e.to_string()));
+                    component_message_handler.abort();
+                    metrics_logger.abort();
+                    return Err(e);
+                }
+            };
+            if bus.connection_state().is_connected() {
+                attempt = 0;
+            }
+            set_connection_state(bus, ConnectionState::Disconnected(reason));
+
+            attempt += 1;
+            let delay = reconnect_delay(attempt);
+            println!("reconnecting to Twitch in {}s", delay.as_secs());
+            tokio::time::sleep(delay).await;
+            set_connection_state(bus, ConnectionState::Reconnecting { attempt });
+            // dropping the old client closes its connections
+            let (incoming, client) = new_client::<T, L>(&login_credentials);
+            incoming_messages = incoming;
+            client_sender.send_replace(client);
+        }
+    }
+
+    pub fn get_msg_dispatcher(&self) -> MessageDispatcher {
+        self.bus.subscriber("irc core")
+    }
+
+    /// Connect a component named `name` to the IRC core, see `EventBus::metrics`.
+    pub fn subscribe(&self, name: &str) -> MessageDispatcher {
+        self.bus.subscriber(name)
+    }
+
+    /// Broadcast messages from the server to all components until the connection is lost, and
+    /// return why. Fails if Twitch rejects the login.
+    pub async fn server_message_handler(
+        incoming_messages: &mut mpsc::UnboundedReceiver<ServerMessage>,
+        bus: &EventBus,
+        rate_limiter: &Mutex<RateLimiter>,
+        login_timeout: Duration,
+    ) -> Result<String> {
+        // when the connection must have logged in by, unless it has
+        let mut login_deadline = Some(Instant::now() + login_timeout);
+        loop {
+            let wait = match login_deadline {
+                Some(deadline) => deadline
+                    .saturating_duration_since(Instant::now())
+                    .min(SILENCE_TIMEOUT),
+                None => SILENCE_TIMEOUT,
+            };
+            let message = match tokio::time::timeout(wait, incoming_messages.recv()).await {
+                Ok(Some(message)) => message,
+                Ok(None) => return Ok("connection closed".to_string()),
+                Err(_) if matches!(login_deadline, Some(deadline) if Instant::now() >= deadline) => {
+                    return Ok(format!(
+                        "not logged in within {}s",
+                        login_timeout.as_secs_f32()
+                    ))
+                }
+                Err(_) => {
+                    return Ok(format!(
+                        "nothing received from Twitch for {}s",
+                        SILENCE_TIMEOUT.as_secs()
+                    ))
+                }
+            };
+            match message {
+                ServerMessage::Privmsg(msg) => {
+                    println!(
+                        "[{}] {}: {}",
+                        msg.channel_login, msg.sender.login, msg.message_text
+                    );
+                    if bus.publish(ServerMessage::Privmsg(msg)) == 0 {
+                        println!("failed to broadcast message: no components subscribed");
+                    }
+                }
+                ServerMessage::Join(msg) => {
+                    println!("[{}] JOIN", msg.channel_login);
+                    // match client
+                    //     .say(
+                    //         msg.channel_login.clone(),
+                    //         "hello i am tw1tchymcbotface, humble servant of all stream viewers"
+                    //             .to_string(),
+                    //     )
+                    //     .await
+                    // {
+                    //     Err(e) => {
+                    //         println!("failed to send bot intro message to channel: {}", e)
+                    //     }
+                    //     _ => (),
+                    // }
+                }
+                ServerMessage::UserState(msg) => {
+                    lock_rate_limiter(rate_limiter).update_user_state(&msg)
+                }
+                ServerMessage::GlobalUserState(msg) => {
+                    println!("logged in as {}", msg.user_name);
+                    login_deadline = None;
+                    set_connection_state(bus, ConnectionState::Connected);
+                    if bus.publish(ServerMessage::GlobalUserState(msg)) == 0 {
+                        println!("failed to broadcast login: no components subscribed");
+                    }
+                }
+                // the client reconnects by itself, and is connected again on the next login, unless
+                // that doesn't happen in time
+                ServerMessage::Reconnect(_) => {
+                    println!("Twitch asked to reconnect");
+                    login_deadline = Some(Instant::now() + login_timeout);
+                    set_connection_state(bus, ConnectionState::Reconnecting { attempt: 1 });
+                }
+                // the client answers pings, and replaces connections that stop answering its own,
+                // but either still shows the connection is alive
+                ServerMessage::Ping(_) | ServerMessage::Pong(_) => (),
+                // a notice outside of any channel before logging in means the login failed
+                ServerMessage::Notice(msg)
+                    if msg.channel_login.is_none() && !bus.connection_state().is_connected() =>
+                {
+                    return Err(Error::TwitchLoginError(msg.message_text));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    pub async fn component_message_handler(
+        clients: watch::Receiver<TwitchIRCClient<T, L>>,
+        mut receiver: mpsc::Receiver<ComponentMessage>,
+        rate_limiter: Arc<Mutex<RateLimiter>>,
+        queue_policy: QueuePolicy,
+    ) {
+        // each channel is joined, sent its messages and left by a task of its own, so waiting for
+        // one channel's rate limit doesn't hold up the others, and joining and leaving a channel
+        // happen in the order asked for
+        let mut channels: HashMap<String, OpenChannel> = HashMap::new();
+        // sender tasks of channels being left, which a task joining them again waits for
+        let mut leaving: HashMap<String, JoinHandle<()>> = HashMap::new();
+        let open = |channel: &str, previous: Option<JoinHandle<()>>| {
+            let queue = Arc::new(ChannelQueue {
+                messages: Mutex::new(OutgoingQueue::new(queue_policy)),
+                notify: Notify::new(),
+                join: AtomicBool::new(false),
+                closed: AtomicBool::new(false),
+            });
+            let sender = tokio::spawn(Self::channel_sender(
+                channel.to_string(),
+                queue.clone(),
+                previous,
+                clients.clone(),
+                rate_limiter.clone(),
+            ));
+            OpenChannel { queue, sender }
+        };
+        while let Some(message) = receiver.recv().await {
+            let channel = match message {
+                ComponentMessage::JoinChannel(msg) => {
+                    let channel = channels
+                        .entry(msg.channel.clone())
+                        .or_insert_with(|| open(&msg.channel, leaving.remove(&msg.channel)));
+                    channel.queue.join();
+                    continue;
+                }
+                ComponentMessage::PartChannel(msg) => {
+                    // the channel's sender task sends what's already queued, then leaves it
+                    match channels.remove(&msg.channel) {
+                        Some(channel) => {
+                            channel.queue.close();
+                            leaving.insert(msg.channel, channel.sender);
+                        }
+                        None => println!("not in {}, nothing to leave", msg.channel),
+                    }
+                    continue;
+                }
+                // whispers may be sent from any channel, by convention #jtv
+                ComponentMessage::Whisper(_) => WHISPER_CHANNEL.to_string(),
+                ComponentMessage::Chat(ChatMessage { ref channel, .. })
+                | ComponentMessage::Announcement(ChatMessage { ref channel, .. })
+                | ComponentMessage::Action(ChatMessage { ref channel, .. })
+                | ComponentMessage::Reply(ReplyMessage { ref channel, .. })
+                | ComponentMessage::Moderate(ModerationMessage { ref channel, .. }) => {
+                    channel.clone()
+                }
+            };
+
+            let queue = match channels.get(&channel) {
+                Some(open) => &open.queue,
+                // whispers are sent without joining
+                None if channel == WHISPER_CHANNEL => {
+                    &channels
+                        .entry(channel.clone())
+                        .or_insert_with(|| open(&channel, None))
+                        .queue
+                }
+                None => {
+                    println!("[{}] not in the channel, dropped {:?}", channel, message);
+                    continue;
+                }
+            };
+            let dropped = lock_queue(&queue.messages).push(message);
+            if let Some(dropped) = dropped {
+                println!("[{}] outgoing queue full, dropped {:?}", channel, dropped);
+            }
+            queue.notify.notify_one();
+        }
+    }
+
+    async fn channel_sender(
+        channel: String,
+        queue: Arc<ChannelQueue>,
+        previous: Option<JoinHandle<()>>,
+        clients: watch::Receiver<TwitchIRCClient<T, L>>,
+        rate_limiter: Arc<Mutex<RateLimiter>>,
+    ) {
+        // the channel is left by the previous sender task before it's joined again
+        if let Some(previous) = previous {
+            let _ = previous.await;
+        }
+        loop {
+            if queue.join.swap(false, Ordering::Relaxed) {
+                rate_limit(&rate_limiter, Traffic::Join, &channel).await;
+                let client = clients.borrow().clone();
+                if let Err(e) = client.join(channel.clone()) {
+                    println!("failed to join {}: {}", channel, e);
+                }
+                continue;
+            }
+            let (message, stale) = lock_queue(&queue.messages).pop();
+            for message in stale.iter() {
+                println!("[{}] dropped stale outgoing message {:?}", channel, message);
+            }
+            match message {
+                Some(message) => {
+                    let client = clients.borrow().clone();
+                    let parts = Self::send_message(&client, &rate_limiter, message).await;
+                    log_parts(&channel, parts);
+                }
+                None if queue.closed.load(Ordering::Relaxed) => break,
+                None => queue.notify.notified().await,
+            }
+        }
+        clients.borrow().part(channel);
+    }
+
+    /// Send `message`, split into as many parts as needed, returning the number of parts.
+    async fn send_message(
+        client: &TwitchIRCClient<T, L>,
+        rate_limiter: &Mutex<RateLimiter>,
+        message: ComponentMessage,
+    ) -> usize {
+        match message {
+            ComponentMessage::Chat(msg) | ComponentMessage::Announcement(msg) => {
+                let parts = split_message(&msg.message, MAX_MESSAGE_LENGTH - SAY_OVERHEAD);
+                let count = parts.len();
+                for part in parts.into_iter() {
+                    rate_limit(rate_limiter, Traffic::Chat, &msg.channel).await;
+                    if let Err(e) = client.say(msg.channel.clone(), part.clone()).await {
+                        println!("failed to send message {} to {}: {}", part, msg.channel, e);
+                    }
+                }
+                count
+            }
+            ComponentMessage::Reply(msg) => {
+                // every part is threaded as a reply to the same message
+                let parts = split_message(&msg.message, MAX_MESSAGE_LENGTH - SAY_OVERHEAD);
+                let count = parts.len();
+                for part in parts.into_iter() {
+                    rate_limit(rate_limiter, Traffic::Chat, &msg.channel).await;
+                    if let Err(e) = client
+                        .say_in_response(
+                            msg.channel.clone(),
+                            part.clone(),
+                            Some(msg.reply_to_id.clone()),
+                        )
+                        .await
+                    {
+                        println!("failed to send reply {} to {}: {}", part, msg.channel, e);
+                    }
+                }
+                count
+            }
+            ComponentMessage::Action(msg) => {
+                let parts = split_message(&msg.message, MAX_MESSAGE_LENGTH - "/me ".len());
+                let count = parts.len();
+                for part in parts.into_iter() {
+                    rate_limit(rate_limiter, Traffic::Chat, &msg.channel).await;
+                    if let Err(e) = client
+                        .privmsg(msg.channel.clone(), format!("/me {}", part))
+                        .await
+                    {
+                        println!("failed to send action {} to {}: {}", part, msg.channel, e);
+                    }
+                }
+                count
+            }
+            ComponentMessage::Whisper(msg) => {
+                let command = format!("/w {} ", msg.user);
+                let parts = split_message(
+                    &msg.message,
+                    MAX_MESSAGE_LENGTH.saturating_sub(command.chars().count()),
+                );
+                let count = parts.len();
+                for part in parts.into_iter() {
+                    rate_limit(rate_limiter, Traffic::Whisper, &msg.user).await;
+                    if let Err(e) = client
+                        .privmsg(WHISPER_CHANNEL.to_string(), format!("{}{}", command, part))
+                        .await
+                    {
+                        println!("failed to whisper {}: {}", msg.user, e);
+                    }
+                }
+                count
+            }
+            ComponentMessage::Moderate(msg) => {
+                rate_limit(rate_limiter, Traffic::Moderation, &msg.channel).await;
+                let result = match &msg.action {
+                    ModerationAction::Timeout {
+                        user,
+                        duration,
+                        reason,
+                    } => {
+                        client
+                            .timeout(msg.channel.clone(), user, *duration, reason.as_deref())
+                            .await
+                    }
+                    ModerationAction::Untimeout { user } => {
+                        client.untimeout(msg.channel.clone(), user).await
+                    }
+                    ModerationAction::Ban { user, reason } => {
+                        client
+                            .ban(msg.channel.clone(), user, reason.as_deref())
+                            .await
+                    }
+                    ModerationAction::Unban { user } => {
+                        client.unban(msg.channel.clone(), user).await
+                    }
+                    ModerationAction::DeleteMessage { message_id } => {
+                        client
+                            .privmsg(msg.channel.clone(), format!("/delete {}", message_id))
+                            .await
+                    }
+                };
+                if let Err(e) = result {
+                    println!("failed to {:?} in {}: {}", msg.action, msg.channel, e);
+                }
+                1
+            }
+            // handled by `component_message_handler`
+            ComponentMessage::JoinChannel(_) | ComponentMessage::PartChannel(_) => 0,
+        }
+    }
+}
+
+/// Join `channel` and send `message` to it, waiting for Twitch to acknowledge each part, without
+/// the queueing of `IrcCore`. Meant for one-off messages, eg from the command line. Returns the
+/// number of parts the message was split into.
+pub async fn say_once<T: Transport, L: LoginCredentials>(
+    irc_config: ClientConfig<L>,
+    channel: &str,
+    message: &str,
+) -> Result<usize> {
+    let (mut incoming_messages, client) = TwitchIRCClient::<T, L>::new(irc_config);
+    let rate_limiter = Mutex::new(RateLimiter::new());
+
+    // Twitch sends a USERSTATE on joining a channel and after each message sent to it
+    client.join(channel.to_string())?;
+    wait_for_user_state(&mut incoming_messages, channel, &rate_limiter).await?;
+    let parts = split_message(message, MAX_MESSAGE_LENGTH - SAY_OVERHEAD);
+    let count = parts.len();
+    for part in parts.into_iter() {
+        rate_limit(&rate_limiter, Traffic::Chat, channel).await;
+        client
+            .say(channel.to_string(), part)
+            .await
+            // the client's errors depend on its transport and credentials
+            .map_err(|e| Error::TwitchIRCMessageSendError(e.to_string()))?;
+        wait_for_user_state(&mut incoming_messages, channel, &rate_limiter).await?;
+    }
+    Ok(count)
+}
+
+async fn wait_for_user_state(
+    incoming_messages: &mut mpsc::UnboundedReceiver<ServerMessage>,
+    channel: &str,
+    rate_limiter: &Mutex<RateLimiter>,
+) -> Result<()> {
+    let acknowledged = async {
+        while let Some(message) = incoming_messages.recv().await {
+            match message {
+                ServerMessage::UserState(msg) if msg.channel_login == channel => {
+                    lock_rate_limiter(rate_limiter).update_user_state(&msg);
+                    return Ok(());
+                }
+                // eg a failed login, being banned from the channel or a duplicate message
+                ServerMessage::Notice(msg)
+                    if msg.channel_login.is_none()
+                        || msg.channel_login.as_deref() == Some(channel) =>
+                {
+                    return Err(Error::SomethingBad(msg.message_text));
+                }
+                _ => (),
+            }
+        }
+        Err(Error::SomethingBad("connection closed".to_string()))
+    };
+    match tokio::time::timeout(ACK_TIMEOUT, acknowledged).await {
+        Ok(result) => result,
+        Err(_) => Err(Error::SomethingBad(format!(
+            "no response from Twitch in {} within {}s",
+            channel,
+            ACK_TIMEOUT.as_secs()
+        ))),
+    }
+}
+
+fn new_client<T: Transport, L: LoginCredentials + Clone>(
+    login_credentials: &L,
+) -> (
+    mpsc::UnboundedReceiver<ServerMessage>,
+    TwitchIRCClient<T, L>,
+) {
+    TwitchIRCClient::new(ClientConfig::new_simple(login_credentials.clone()))
+}
+
+/// Publish the new connection `state` on `bus`, logging it if it changed.
+fn set_connection_state(bus: &EventBus, state: ConnectionState) {
+    let description = state.to_string();
+    if bus.publish_connection(state) {
+        println!("Twitch connection: {}", description);
+    }
+}
+
+/// Log how each subscriber to `bus` is keeping up, every `METRICS_LOG_INTERVAL`.
+async fn log_metrics(bus: EventBus) {
+    let mut interval = tokio::time::interval(METRICS_LOG_INTERVAL);
+    // the first tick is immediate, when there's nothing to report yet
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        for stats in bus.metrics() {
+            println!(
+                "subscriber {}: {} events received, fell behind {} times, skipping {} events",
+                stats.name, stats.received, stats.lagged, stats.skipped
+            );
+        }
+    }
+}
+
+/// Wait until `rate_limiter` allows sending `traffic` to `channel`.
+async fn rate_limit(rate_limiter: &Mutex<RateLimiter>, traffic: Traffic, channel: &str) {
+    loop {
+        let wait = lock_rate_limiter(rate_limiter).try_acquire(traffic, channel);
+        match wait {
+            Some(wait) => tokio::time::sleep(wait).await,
+            None => return,
+        }
+    }
+}
+
+fn lock_queue(queue: &Mutex<OutgoingQueue>) -> MutexGuard<'_, OutgoingQueue> {
+    queue.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn lock_rate_limiter(rate_limiter: &Mutex<RateLimiter>) -> MutexGuard<'_, RateLimiter> {
+    rate_limiter.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Split `message` into parts of at most `max_length` characters, preferring word boundaries and
+/// only breaking words longer than `max_length`, between graphemes.
+/// Empty or blank messages have no parts.
+pub fn split_message(message: &str, max_length: usize) -> Vec<String> {
+    let message = message.trim();
+    if message.is_empty() {
+        return Vec::new();
+    }
+    if message.chars().count() <= max_length {
+        return vec![message.to_string()];
+    }
+
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut part_length = 0;
+    for word in message.split_word_bounds() {
+        let word_length = word.chars().count();
+        if part_length + word_length > max_length {
+            push_part(&mut parts, &mut part);
+            part_length = 0;
+            // whitespace at a split would only pad the start of the next part
+            if word.trim().is_empty() {
+                continue;
+            }
+            if word_length > max_length {
+                for grapheme in word.graphemes(true) {
+                    let grapheme_length = grapheme.chars().count();
+                    if part_length + grapheme_length > max_length {
+                        push_part(&mut parts, &mut part);
+                        part_length = 0;
+                    }
+                    part.push_str(grapheme);
+                    part_length += grapheme_length;
+                }
+                continue;
+            }
+        }
+        part.push_str(word);
+        part_length += word_length;
+    }
+    push_part(&mut parts, &mut part);
+    parts
+}
+
+fn push_part(parts: &mut Vec<String>, part: &mut String) {
+    let trimmed = part.trim();
+    if !trimmed.is_empty() {
+        parts.push(trimmed.to_string());
+    }
+    part.clear();
+}
+
+fn log_parts(channel: &str, parts: usize) {
+    if parts > 1 {
+        println!("[{}] split message into {} parts", channel, parts);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ComponentMessage {
+    Chat(ChatMessage),
+    /// Chat message sent with the lowest priority, eg a periodic announcement.
+    Announcement(ChatMessage),
+    Reply(ReplyMessage),
+    Action(ChatMessage),
+    Whisper(WhisperMessage),
+    JoinChannel(JoinChannelMessage),
+    PartChannel(PartChannelMessage),
+    Moderate(ModerationMessage),
+}
+
+#[derive(Clone, Debug)]
+pub struct ChatMessage {
+    pub channel: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct JoinChannelMessage {
+    pub channel: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct PartChannelMessage {
+    pub channel: String,
+}
+
+/// Chat message threaded as a reply to the message with ID `reply_to_id`.
+#[derive(Clone, Debug)]
+pub struct ReplyMessage {
+    pub channel: String,
+    pub reply_to_id: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct WhisperMessage {
+    pub user: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct ModerationMessage {
+    pub channel: String,
+    pub action: ModerationAction,
+}
+
+#[derive(Clone, Debug)]
+pub enum ModerationAction {
+    Timeout {
+        user: String,
+        duration: Duration,
+        reason: Option<String>,
+    },
+    Untimeout {
+        user: String,
+    },
+    Ban {
+        user: String,
+        reason: Option<String>,
+    },
+    Unban {
+        user: String,
+    },
+    DeleteMessage {
+        message_id: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn next_state(events: &mut MessageDispatcher) -> ConnectionState {
+        next_connection_state(events).await.unwrap()
+    }
+
+    fn credentials() -> StaticLoginCredentials {
+        StaticLoginCredentials::new("botface".to_string(), Some("token".to_string()))
+    }
+
+    #[tokio::test]
+    async fn reconnects_that_dont_log_in_are_replaced() {
+        let server = FakeTwitchServer::start();
+        let mut core = IrcCore::<InMemoryTransport>::with_transport()
+            .with_login_timeout(Duration::from_millis(200));
+        let mut events = core.subscribe("test");
+        tokio::spawn(async move { core.run_irc(credentials()).await });
+        assert_eq!(next_state(&mut events).await, ConnectionState::Connecting);
+        assert_eq!(next_state(&mut events).await, ConnectionState::Connected);
+
+        server.set_login_response(LoginResponse::Ignore);
+        server.send(":tmi.twitch.tv RECONNECT").unwrap();
+        assert_eq!(
+            next_state(&mut events).await,
+            ConnectionState::Reconnecting { attempt: 1 }
+        );
+        assert!(matches!(
+            next_state(&mut events).await,
+            ConnectionState::Disconnected(reason) if reason.starts_with("not logged in")
+        ));
+        server.set_login_response(LoginResponse::Accept);
+        assert_eq!(
+            next_state(&mut events).await,
+            ConnectionState::Reconnecting { attempt: 1 }
+        );
+        assert_eq!(next_state(&mut events).await, ConnectionState::Connected);
+    }
+
+    #[tokio::test]
+    async fn rejected_logins_stop_the_core() {
+        let server = FakeTwitchServer::start();
+        server.set_login_response(LoginResponse::Reject);
+        let mut core = IrcCore::<InMemoryTransport>::with_transport();
+        let mut events = core.subscribe("test");
+        let result = tokio::spawn(async move { core.run_irc(credentials()).await });
+        assert_eq!(next_state(&mut events).await, ConnectionState::Connecting);
+        assert!(matches!(
+            next_state(&mut events).await,
+            ConnectionState::Disconnected(_)
+        ));
+        assert!(matches!(
+            result.await.unwrap(),
+            Err(Error::TwitchLoginError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn channels_are_joined_and_left_in_order() {
+        let mut server = FakeTwitchServer::start();
+        let mut core = IrcCore::<InMemoryTransport>::with_transport();
+        let mut events = core.subscribe("test");
+        tokio::spawn(async move { core.run_irc(credentials()).await });
+        assert_eq!(next_state(&mut events).await, ConnectionState::Connecting);
+        assert_eq!(next_state(&mut events).await, ConnectionState::Connected);
+
+        let join = || {
+            ComponentMessage::JoinChannel(JoinChannelMessage {
+                channel: "uuayn".to_string(),
+            })
+        };
+        let chat = |message: &str| {
+            ComponentMessage::Chat(ChatMessage {
+                channel: "uuayn".to_string(),
+                message: message.to_string(),
+            })
+        };
+        events.try_send(join()).unwrap();
+        events.try_send(chat("before leaving")).unwrap();
+        events
+            .try_send(ComponentMessage::PartChannel(PartChannelMessage {
+                channel: "uuayn".to_string(),
+            }))
+            .unwrap();
+        events.try_send(chat("after leaving")).unwrap();
+        events.try_send(join()).unwrap();
+        events.try_send(chat("after joining again")).unwrap();
+
+        server
+            .run_script(
+                "< JOIN #uuayn\n\
+                 < PRIVMSG #uuayn :. before leaving\n\
+                 < PART #uuayn\n\
+                 < JOIN #uuayn",
+            )
+            .await
+            .unwrap();
+        // messages for a channel the bot isn't in are dropped
+        let message = server.expect("PRIVMSG #uuayn").await.unwrap();
+        assert_eq!(message.params, vec!["#uuayn", ". after joining again"]);
+    }
+
+    #[test]
+    fn short_messages_are_one_part() {
+        assert_eq!(split_message("  hi chat  ", 10), vec!["hi chat"]);
+        assert_eq!(split_message("exactly10!", 10), vec!["exactly10!"]);
+    }
+
+    #[test]
+    fn blank_messages_have_no_parts() {
+        assert!(split_message("", 10).is_empty());
+        assert!(split_message(" \t ", 10).is_empty());
+    }
+
+    #[test]
+    fn splits_at_word_boundaries() {
+        assert_eq!(
+            split_message("the quick brown fox jumps", 10),
+            vec!["the quick", "brown fox", "jumps"]
+        );
+    }
+
+    #[test]
+    fn breaks_long_words_between_graphemes() {
+        // a family emoji is one grapheme of five characters
+        let family = "👨‍👩‍👧";
+        let parts = split_message(&format!("ab {}{}", family, family), 6);
+        assert_eq!(parts, vec!["ab", family, family]);
+
+        let parts = split_message("abcdefghij", 4);
+        assert_eq!(parts, vec!["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn parts_fit_the_limit() {
+        let message = "lorem ipsum dolor sit amet, consectetur adipiscing elit ".repeat(30);
+        let parts = split_message(&message, MAX_MESSAGE_LENGTH - SAY_OVERHEAD);
+        assert!(parts.len() > 1);
+        for part in parts.iter() {
+            assert!(part.chars().count() <= MAX_MESSAGE_LENGTH - SAY_OVERHEAD);
+            assert_eq!(part.trim(), part);
+        }
+        assert_eq!(
+            parts.join(" ").split_whitespace().collect::<Vec<_>>(),
+            message.split_whitespace().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::irc::ComponentMessage;
+
+// defaults of `QueuePolicy`
+const DEFAULT_MAX_DEPTH: usize = 50;
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(60);
+
+/// Order in which queued messages to a channel are sent, highest first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Announcement,
+    Command,
+    Moderation,
+}
+
+impl ComponentMessage {
+    pub fn priority(&self) -> Priority {
+        match self {
+            ComponentMessage::Moderate(_) => Priority::Moderation,
+            ComponentMessage::Announcement(_) => Priority::Announcement,
+            _ => Priority::Command,
+        }
+    }
+}
+
+/// Which message to drop when a message arrives at a full queue. Either way only messages of the
+/// lowest priority queued are dropped, and the arriving message is dropped instead if it has an
+/// even lower priority.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DropPolicy {
+    /// Drop the oldest message, favouring fresh responses.
+    Oldest,
+    /// Drop the newest message, favouring messages in the order they were sent.
+    Newest,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct QueuePolicy {
+    /// Maximum number of messages waiting to be sent to a channel.
+    pub max_depth: usize,
+    /// Messages below moderation priority waiting longer than this are dropped instead of sent,
+    /// eg a reply to a command nobody remembers invoking.
+    pub max_age: Option<Duration>,
+    pub drop_policy: DropPolicy,
+}
+
+impl Default for QueuePolicy {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_age: Some(DEFAULT_MAX_AGE),
+            drop_policy: DropPolicy::Oldest,
+        }
+    }
+}
+
+struct Queued {
+    message: ComponentMessage,
+    priority: Priority,
+    enqueued: Instant,
+}
+
+/// Messages waiting to be sent to one channel.
+pub struct OutgoingQueue {
+    policy: QueuePolicy,
+    messages: VecDeque<Queued>,
+}
+
+impl OutgoingQueue {
+    pub fn new(policy: QueuePolicy) -> Self {
+        Self {
+            policy,
+            messages: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Queue `message`, returning the message dropped to make room for it, if any.
+    pub fn push(&mut self, message: ComponentMessage) -> Option<ComponentMessage> {
+        let queued = Queued {
+            priority: message.priority(),
+            message,
+            enqueued: Instant::now(),
+        };
+        if self.messages.len() < self.policy.max_depth {
+            self.messages.push_back(queued);
+            return None;
+        }
+
+        let lowest = self.messages.iter().map(|queued| queued.priority).min();
+        let lowest = match lowest {
+            Some(lowest) if lowest <= queued.priority => lowest,
+            // nothing queued that's less important, or a queue depth of 0
+            _ => return Some(queued.message),
+        };
+        let victim = match self.policy.drop_policy {
+            DropPolicy::Oldest => self.messages.iter().position(|q| q.priority == lowest),
+            DropPolicy::Newest if lowest == queued.priority => return Some(queued.message),
+            DropPolicy::Newest => self.messages.iter().rposition(|q| q.priority == lowest),
+        };
+        let dropped = victim.and_then(|index| self.messages.remove(index));
+        self.messages.push_back(queued);
+        dropped.map(|dropped| dropped.message)
+    }
+
+    /// Take the highest priority message, the oldest one among those of the same priority. Stale
+    /// messages are dropped and returned separately.
+    pub fn pop(&mut self) -> (Option<ComponentMessage>, Vec<ComponentMessage>) {
+        let mut stale = Vec::new();
+        if let Some(max_age) = self.policy.max_age {
+            let now = Instant::now();
+            let mut index = 0;
+            while index < self.messages.len() {
+                let queued = &self.messages[index];
+                if queued.priority < Priority::Moderation
+                    && now.saturating_duration_since(queued.enqueued) > max_age
+                {
+                    if let Some(queued) = self.messages.remove(index) {
+                        stale.push(queued.message);
+                    }
+                } else {
+                    index += 1;
+                }
+            }
+        }
+
+        let highest = self.messages.iter().map(|queued| queued.priority).max();
+        let next = highest
+            .and_then(|highest| self.messages.iter().position(|q| q.priority == highest))
+            .and_then(|index| self.messages.remove(index))
+            .map(|queued| queued.message);
+        (next, stale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irc::{ChatMessage, ModerationAction, ModerationMessage};
+
+    fn chat(message: &str) -> ComponentMessage {
+        ComponentMessage::Chat(ChatMessage {
+            channel: "uuayn".to_string(),
+            message: message.to_string(),
+        })
+    }
+
+    fn announcement(message: &str) -> ComponentMessage {
+        ComponentMessage::Announcement(ChatMessage {
+            channel: "uuayn".to_string(),
+            message: message.to_string(),
+        })
+    }
+
+    fn moderation(user: &str) -> ComponentMessage {
+        ComponentMessage::Moderate(ModerationMessage {
+            channel: "uuayn".to_string(),
+            action: ModerationAction::Untimeout {
+                user: user.to_string(),
+            },
+        })
+    }
+
+    fn text(message: ComponentMessage) -> String {
+        match message {
+            ComponentMessage::Chat(msg) | ComponentMessage::Announcement(msg) => msg.message,
+            ComponentMessage::Moderate(ModerationMessage {
+                action: ModerationAction::Untimeout { user },
+                ..
+            }) => user,
+            message => panic!("unexpected message {:?}", message),
+        }
+    }
+
+    fn queue(max_depth: usize, drop_policy: DropPolicy) -> OutgoingQueue {
+        OutgoingQueue::new(QueuePolicy {
+            max_depth,
+            max_age: None,
+            drop_policy,
+        })
+    }
+
+    fn drain(queue: &mut OutgoingQueue) -> Vec<String> {
+        std::iter::from_fn(|| queue.pop().0).map(text).collect()
+    }
+
+    #[test]
+    fn full_queues_drop_the_oldest() {
+        let mut queue = queue(2, DropPolicy::Oldest);
+        assert!(queue.push(chat("one")).is_none());
+        assert!(queue.push(chat("two")).is_none());
+        assert_eq!(queue.push(chat("three")).map(text).as_deref(), Some("one"));
+        assert_eq!(drain(&mut queue), vec!["two", "three"]);
+    }
+
+    #[test]
+    fn full_queues_drop_the_newest() {
+        let mut queue = queue(2, DropPolicy::Newest);
+        assert!(queue.push(chat("one")).is_none());
+        assert!(queue.push(announcement("two")).is_none());
+        // the announcement is less important than the arriving message
+        assert_eq!(queue.push(chat("three")).map(text).as_deref(), Some("two"));
+        assert_eq!(queue.push(chat("four")).map(text).as_deref(), Some("four"));
+        assert_eq!(drain(&mut queue), vec!["one", "three"]);
+    }
+
+    #[test]
+    fn full_queues_keep_more_important_messages() {
+        let mut queue = queue(1, DropPolicy::Oldest);
+        assert!(queue.push(moderation("someone")).is_none());
+        assert_eq!(queue.push(chat("one")).map(text).as_deref(), Some("one"));
+        assert_eq!(drain(&mut queue), vec!["someone"]);
+    }
+
+    #[test]
+    fn more_important_messages_are_sent_first() {
+        let mut queue = queue(10, DropPolicy::Oldest);
+        queue.push(announcement("announcement"));
+        queue.push(chat("one"));
+        queue.push(moderation("someone"));
+        queue.push(chat("two"));
+        assert_eq!(
+            drain(&mut queue),
+            vec!["someone", "one", "two", "announcement"]
+        );
+    }
+
+    #[test]
+    fn stale_messages_are_dropped() {
+        let mut queue = OutgoingQueue::new(QueuePolicy {
+            max_depth: 10,
+            max_age: Some(Duration::ZERO),
+            drop_policy: DropPolicy::Oldest,
+        });
+        queue.push(chat("one"));
+        queue.push(moderation("someone"));
+        std::thread::sleep(Duration::from_millis(1));
+        let (next, stale) = queue.pop();
+        // moderation is sent however long it waited
+        assert_eq!(next.map(text).as_deref(), Some("someone"));
+        assert_eq!(stale.into_iter().map(text).collect::<Vec<_>>(), vec!["one"]);
+        assert!(queue.is_empty());
+    }
+}
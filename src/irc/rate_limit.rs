@@ -0,0 +1,325 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use twitch_irc::message::UserStateMessage;
+
+// PRIVMSG limits across all channels, depending on whether the bot is a moderator, VIP or the
+// broadcaster in the channel it sends to
+const CHAT_LIMIT: u32 = 20;
+const PRIVILEGED_CHAT_LIMIT: u32 = 100;
+const CHAT_PERIOD: Duration = Duration::from_secs(30);
+
+// minimum time between two messages in the same channel when the bot isn't privileged there
+const UNPRIVILEGED_CHANNEL_GAP: Duration = Duration::from_secs(1);
+
+const JOIN_LIMIT: u32 = 20;
+const JOIN_PERIOD: Duration = Duration::from_secs(10);
+
+// whispers may burst to 3 per second, but no more than 100 per minute
+const WHISPER_BURST: u32 = 3;
+const WHISPER_LIMIT: u32 = 100;
+const WHISPER_PERIOD: Duration = Duration::from_secs(60);
+
+// share of the PRIVMSG buckets only moderation actions may use, so chatter can't starve them
+const MODERATION_RESERVE: f64 = 0.1;
+
+/// Kinds of outgoing traffic that Twitch limits separately.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Traffic {
+    Chat,
+    /// Counts against the chat limits like `Chat`, but may also use the share of them kept back
+    /// from chat.
+    Moderation,
+    Whisper,
+    Join,
+}
+
+/// Classic token bucket: holds up to `capacity` tokens, refilled continuously at `rate` tokens per
+/// second.
+#[derive(Clone, Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    rate: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Bucket allowing `limit` tokens to be taken per `period`, starting full.
+    pub fn new(limit: u32, period: Duration) -> Self {
+        Self::with_burst(limit, limit, period)
+    }
+
+    /// Bucket allowing `limit` tokens to be taken per `period`, but no more than `burst` at once.
+    pub fn with_burst(burst: u32, limit: u32, period: Duration) -> Self {
+        Self {
+            capacity: burst as f64,
+            tokens: burst as f64,
+            rate: limit as f64 / period.as_secs_f64(),
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// How long until a token can be taken while leaving `reserve` tokens in the bucket.
+    fn wait_time(&mut self, reserve: f64, now: Instant) -> Duration {
+        self.refill(now);
+        let missing = 1.0 + reserve - self.tokens;
+        if missing <= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(missing / self.rate)
+    }
+
+    fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+}
+
+/// Outgoing rate limits of Twitch IRC, see <https://dev.twitch.tv/docs/irc#rate-limits>.
+///
+/// The bot is assumed unprivileged in a channel until a USERSTATE for it says otherwise.
+///
+/// Moderation actions take priority over chat only in that chat may not use the last tenth of
+/// the chat limits, leaving them to moderation when chat is busy. The limiter doesn't reorder
+/// anything: the order messages to a channel are sent in is up to its `OutgoingQueue`, which
+/// sends moderation actions first.
+pub struct RateLimiter {
+    chat: TokenBucket,
+    privileged_chat: TokenBucket,
+    whispers: TokenBucket,
+    joins: TokenBucket,
+    // channels the bot is a moderator, VIP or the broadcaster in
+    privileged_channels: HashSet<String>,
+    last_sent: HashMap<String, Instant>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self {
+            chat: TokenBucket::new(CHAT_LIMIT, CHAT_PERIOD),
+            privileged_chat: TokenBucket::new(PRIVILEGED_CHAT_LIMIT, CHAT_PERIOD),
+            whispers: TokenBucket::with_burst(WHISPER_BURST, WHISPER_LIMIT, WHISPER_PERIOD),
+            joins: TokenBucket::new(JOIN_LIMIT, JOIN_PERIOD),
+            privileged_channels: HashSet::new(),
+            last_sent: HashMap::new(),
+        }
+    }
+
+    pub fn is_privileged(&self, channel: &str) -> bool {
+        self.privileged_channels.contains(channel)
+    }
+
+    /// Update the bot's status in a channel from the USERSTATE Twitch sends on joining and after
+    /// each message the bot sends.
+    pub fn update_user_state(&mut self, msg: &UserStateMessage) {
+        let privileged = msg
+            .badges
+            .iter()
+            .any(|badge| matches!(badge.name.as_str(), "broadcaster" | "moderator" | "vip"));
+        let was_privileged = if privileged {
+            !self.privileged_channels.insert(msg.channel_login.clone())
+        } else {
+            self.privileged_channels.remove(&msg.channel_login)
+        };
+        if privileged != was_privileged {
+            println!(
+                "[{}] rate limit now {} messages per {}s",
+                msg.channel_login,
+                if privileged {
+                    PRIVILEGED_CHAT_LIMIT
+                } else {
+                    CHAT_LIMIT
+                },
+                CHAT_PERIOD.as_secs()
+            );
+        }
+    }
+
+    /// Take a token for sending `traffic` to `channel`, or return how long to wait before trying
+    /// again.
+    pub fn try_acquire(&mut self, traffic: Traffic, channel: &str) -> Option<Duration> {
+        self.try_acquire_at(traffic, channel, Instant::now())
+    }
+
+    fn try_acquire_at(
+        &mut self,
+        traffic: Traffic,
+        channel: &str,
+        now: Instant,
+    ) -> Option<Duration> {
+        let wait = match traffic {
+            Traffic::Join => self.joins.wait_time(0.0, now),
+            Traffic::Whisper => self.whispers.wait_time(0.0, now),
+            Traffic::Chat | Traffic::Moderation => self.chat_wait_time(traffic, channel, now),
+        };
+        if !wait.is_zero() {
+            return Some(wait);
+        }
+
+        match traffic {
+            Traffic::Join => self.joins.take(),
+            Traffic::Whisper => self.whispers.take(),
+            Traffic::Chat | Traffic::Moderation => {
+                self.privileged_chat.take();
+                if !self.is_privileged(channel) {
+                    self.chat.take();
+                }
+                self.last_sent.insert(channel.to_string(), now);
+            }
+        }
+        None
+    }
+
+    fn chat_wait_time(&mut self, traffic: Traffic, channel: &str, now: Instant) -> Duration {
+        let reserve = |bucket: &TokenBucket| match traffic {
+            Traffic::Moderation => 0.0,
+            _ => bucket.capacity * MODERATION_RESERVE,
+        };
+
+        let privileged_reserve = reserve(&self.privileged_chat);
+        let mut wait = self.privileged_chat.wait_time(privileged_reserve, now);
+        if self.is_privileged(channel) {
+            return wait;
+        }
+
+        let chat_reserve = reserve(&self.chat);
+        wait = wait.max(self.chat.wait_time(chat_reserve, now));
+        if let Some(last_sent) = self.last_sent.get(channel) {
+            let since = now.saturating_duration_since(*last_sent);
+            wait = wait.max(UNPRIVILEGED_CHANNEL_GAP.saturating_sub(since));
+        }
+        wait
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use twitch_irc::message::IRCMessage;
+
+    use super::*;
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    fn user_state(channel: &str, badges: &str) -> UserStateMessage {
+        let line = format!(
+            "@badge-info=;badges={};color=;display-name=Bot;emote-sets=0;mod=0;subscriber=0;user-type= :tmi.twitch.tv USERSTATE #{}",
+            badges, channel
+        );
+        UserStateMessage::try_from(IRCMessage::parse(&line).unwrap()).unwrap()
+    }
+
+    // take tokens one after another at `now`, returning how many could be taken
+    fn drain(limiter: &mut RateLimiter, traffic: Traffic, channel: &str, now: Instant) -> u32 {
+        let mut taken = 0;
+        while limiter.try_acquire_at(traffic, channel, now).is_none() {
+            taken += 1;
+        }
+        taken
+    }
+
+    #[test]
+    fn bucket_refills_continuously() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(20, Duration::from_secs(30));
+        bucket.last_refill = start;
+        assert_eq!(bucket.wait_time(0.0, start), Duration::ZERO);
+        for _ in 0..20 {
+            bucket.take();
+        }
+        // 20 per 30s is a token every 1.5s
+        assert_eq!(bucket.wait_time(0.0, start), secs(1.5));
+        assert_eq!(bucket.wait_time(0.0, start + secs(0.5)), secs(1.0));
+        assert_eq!(bucket.wait_time(0.0, start + secs(1.5)), Duration::ZERO);
+        // never beyond capacity
+        assert_eq!(bucket.wait_time(0.0, start + secs(3600.0)), Duration::ZERO);
+        assert_eq!(bucket.tokens, 20.0);
+    }
+
+    #[test]
+    fn bucket_keeps_the_reserve() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(20, Duration::from_secs(30));
+        bucket.last_refill = start;
+        for _ in 0..18 {
+            bucket.take();
+        }
+        assert_eq!(bucket.wait_time(0.0, start), Duration::ZERO);
+        assert_eq!(bucket.wait_time(2.0, start), secs(1.5));
+    }
+
+    #[test]
+    fn burst_is_capped() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::with_burst(3, 100, Duration::from_secs(60));
+        bucket.last_refill = start;
+        for _ in 0..3 {
+            bucket.take();
+        }
+        assert_eq!(bucket.wait_time(0.0, start), secs(0.6));
+        assert_eq!(bucket.wait_time(0.0, start + secs(60.0)), Duration::ZERO);
+        assert_eq!(bucket.tokens, 3.0);
+    }
+
+    #[test]
+    fn unprivileged_channels_wait_between_messages() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new();
+        assert_eq!(limiter.try_acquire_at(Traffic::Chat, "chan", now), None);
+        assert_eq!(
+            limiter.try_acquire_at(Traffic::Chat, "chan", now + secs(0.25)),
+            Some(secs(0.75))
+        );
+        // other channels have their own gap
+        assert_eq!(limiter.try_acquire_at(Traffic::Chat, "other", now), None);
+    }
+
+    #[test]
+    fn chat_leaves_a_reserve_for_moderation() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new();
+        limiter.update_user_state(&user_state("chan", "moderator/1"));
+        assert!(limiter.is_privileged("chan"));
+        // a tenth of the 100 message limit is kept from chat
+        assert_eq!(drain(&mut limiter, Traffic::Chat, "chan", now), 90);
+        assert_eq!(drain(&mut limiter, Traffic::Moderation, "chan", now), 10);
+    }
+
+    #[test]
+    fn privileged_channels_get_the_higher_limit() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new();
+        limiter.update_user_state(&user_state("modded", "vip/1"));
+        limiter.update_user_state(&user_state("plain", ""));
+        assert!(!limiter.is_privileged("plain"));
+        assert!(limiter.is_privileged("modded"));
+
+        // unprivileged messages count against the limit of 20 as well as the limit of 100,
+        // spread over channels to get around the gap between messages to one channel
+        let sent: u32 = (0..30)
+            .map(|n| drain(&mut limiter, Traffic::Chat, &format!("plain{}", n), now))
+            .sum();
+        assert_eq!(sent, 18);
+        assert_eq!(drain(&mut limiter, Traffic::Chat, "plain", now), 0);
+        assert_eq!(drain(&mut limiter, Traffic::Chat, "modded", now), 90 - 18);
+
+        // losing the badge brings the lower limit back
+        limiter.update_user_state(&user_state("modded", ""));
+        assert!(!limiter.is_privileged("modded"));
+    }
+}
@@ -1,4 +1,7 @@
+pub mod announcements;
+pub mod channels;
 pub mod commander;
+pub mod config;
 pub mod egui_ui;
 pub mod error;
 pub mod ndi;
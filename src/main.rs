@@ -1,30 +1,139 @@
+use std::collections::BTreeSet;
 use std::fs::File;
+use std::future::Future;
 use std::io::Read;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
 
-use futures::future::join5;
-use tokio::sync::mpsc;
+use clap::{Args, Parser, Subcommand};
+use tokio::sync::{mpsc, watch};
 
 use twitch_irc::login::StaticLoginCredentials;
-use twitch_irc::ClientConfig;
+use twitch_irc::{ClientConfig, SecureTCPTransport};
 
 use glutin::event_loop::EventLoopProxy;
 
-use tmbf::commander::{CommanderComposer, HardCodedCommander};
-use tmbf::egui_ui::{Botface, BotfaceEvent, ChatboxDispatcher, ChatboxState};
-use tmbf::error::Result;
-use tmbf::irc::{ComponentMessage, IrcCore, JoinChannelMessage, MessageDispatcher};
+use tmbf::announcements::{self, Announcer};
+use tmbf::channels::ChannelManager;
+use tmbf::commander::{
+    AsyncIrcCommander, ChannelCommander, CommandRegistry, CommanderComposer, CounterCommander,
+    Counters, CustomCommander, HardCodedCommander, QuoteCommander, SyncCommander, Triggers,
+    DEFAULT_PREFIX,
+};
+use tmbf::config::Config;
+use tmbf::egui_ui::{
+    Botface, BotfaceEvent, ChatboxDispatcher, ChatboxState, ConnectionDispatcher,
+    CountersDispatcher, CountersState,
+};
+use tmbf::error::{Error, Result};
+use tmbf::irc::{self, ConnectionState, IrcCore};
 use tmbf::ndi::{NDIFrameData, NDIPainter};
 
+/// A Twitch chat bot with a chat box overlay sent over NDI.
+#[derive(Parser)]
+#[clap(name = "twitchy-mcbotface", version)]
+struct Cli {
+    /// Configuration file to use instead of the one in the XDG config directory
+    #[clap(long, global = true, value_parser)]
+    config: Option<PathBuf>,
+    #[clap(flatten)]
+    run_options: RunOptions,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the bot, the default
+    Run,
+    /// Load the configuration and every file it refers to, then exit
+    CheckConfig,
+    /// Print the commands the bot responds to in each channel
+    ListCommands,
+    /// Send a single message to a channel, then exit
+    Send {
+        channel: String,
+        #[clap(required = true)]
+        message: Vec<String>,
+    },
+    /// Add the quotes from a YAML list of quotes, eg exported from another bot, skipping those
+    /// already present
+    ImportQuotes {
+        #[clap(value_parser)]
+        file: PathBuf,
+    },
+    /// Write all quotes to a file as a YAML list of quotes
+    ExportQuotes {
+        #[clap(value_parser)]
+        file: PathBuf,
+    },
+}
+
+// global, so they're also accepted without the `run` subcommand, but ignored by the others
+#[derive(Args)]
+struct RunOptions {
+    /// Run the bot without a window, and so without the NDI chat box
+    #[clap(long, global = true)]
+    headless: bool,
+    /// Run the bot without sending the chat box over NDI, for machines without the NDI runtime
+    #[clap(long, global = true)]
+    no_ndi: bool,
+}
+
+// what the async components share with the window
+struct Ui {
+    event_loop_proxy: EventLoopProxy<BotfaceEvent>,
+    chatbox_state: Arc<Mutex<ChatboxState>>,
+    counters_state: Arc<Mutex<CountersState>>,
+    connection_state: Arc<Mutex<ConnectionState>>,
+}
+
+// commanders in the order they're consulted, and what they share with other components
+struct Commanders {
+    commanders: Vec<Arc<dyn AsyncIrcCommander>>,
+    counters: watch::Receiver<Counters>,
+    channels: watch::Receiver<BTreeSet<String>>,
+    registry: CommandRegistry,
+}
+
 fn main() -> Result<()> {
-    let (frame_sender, frame_receiver) = mpsc::unbounded_channel::<NDIFrameData>();
-    let botface = Botface::new(frame_sender)?;
-    let chatbox_state = botface.chatbox_state();
-    let event_loop_proxy = botface.event_loop_proxy();
+    let cli = Cli::parse();
+    let config_path = Config::locate(cli.config.clone())?;
+    let config = Config::load(cli.config)?;
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => run(config, cli.run_options),
+        Command::CheckConfig => check_config(config, config_path),
+        Command::ListCommands => list_commands(&config),
+        Command::Send { channel, message } => send(config, channel, message.join(" ")),
+        Command::ImportQuotes { file } => import_quotes(&config, file),
+        Command::ExportQuotes { file } => export_quotes(&config, file),
+    }
+}
+
+fn run(config: Config, options: RunOptions) -> Result<()> {
+    config.validate_channels()?;
+    if options.headless {
+        return all_the_async_things(config, None, None);
+    }
+
+    let (frame_sender, frame_receiver) = if options.no_ndi {
+        (None, None)
+    } else {
+        let (frame_sender, frame_receiver) = mpsc::unbounded_channel::<NDIFrameData>();
+        (Some(frame_sender), Some(frame_receiver))
+    };
+    config.validate_ui()?;
+    let botface = Botface::new(frame_sender, config.ui.clone())?;
+    let ui = Ui {
+        event_loop_proxy: botface.event_loop_proxy(),
+        chatbox_state: botface.chatbox_state(),
+        counters_state: botface.counters_state(),
+        connection_state: botface.connection_state(),
+    };
     thread::spawn(move || {
-        if let Err(error) = all_the_async_things(frame_receiver, event_loop_proxy, chatbox_state) {
+        if let Err(error) = all_the_async_things(config, Some(ui), frame_receiver) {
             println!("all (or some) of the async things failed: {}", error);
         }
     });
@@ -32,69 +141,275 @@ fn main() -> Result<()> {
     botface.run_event_loop()
 }
 
+fn check_config(config: Config, config_path: PathBuf) -> Result<()> {
+    config.validate_channels()?;
+    config.validate_ui()?;
+    load_credentials(&config)?;
+    load_commanders(&config)?;
+    if let Some(triggers) = &config.commands.triggers {
+        Triggers::from_file(triggers)?;
+    }
+    if let Some(announcements) = &config.commands.announcements {
+        announcements::load_announcements(announcements)?;
+    }
+    println!("{} is valid", config_path.display());
+    Ok(())
+}
+
+fn list_commands(config: &Config) -> Result<()> {
+    let Commanders { commanders, .. } = load_commanders(config)?;
+    for channel in config.channels.iter() {
+        let prefixes = channel
+            .prefixes
+            .clone()
+            .unwrap_or_else(|| vec![DEFAULT_PREFIX.to_string()]);
+        println!("#{} (prefixes: {})", channel.name, prefixes.join(" "));
+        let prefix = prefixes.first().map_or(DEFAULT_PREFIX, String::as_str);
+        let enabled = commanders
+            .iter()
+            .filter(|commander| match &channel.commanders {
+                Some(names) => names.iter().any(|name| name == commander.name()),
+                None => true,
+            });
+        for commander in enabled {
+            let mut commands = commander.get_commands();
+            commands.sort();
+            for cmd in commands.iter() {
+                let usage = commander.usage(cmd, prefix);
+                match commander.description(cmd) {
+                    Some(description) => println!("  {} - {}", usage, description),
+                    None => println!("  {}", usage),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 #[tokio::main]
-pub async fn all_the_async_things(
-    frame_receiver: mpsc::UnboundedReceiver<NDIFrameData>,
-    event_loop_proxy: EventLoopProxy<BotfaceEvent>,
-    chatbox_state: Arc<Mutex<ChatboxState>>,
-) -> Result<()> {
-    let mut file = File::open("/home/wayne/.config/twitchy-mcbotface/auth.yml")?;
-    let mut contents = String::new();
+async fn send(config: Config, channel: String, message: String) -> Result<()> {
+    let login_creds = load_credentials(&config)?;
+    let parts = irc::say_once::<SecureTCPTransport, _>(
+        ClientConfig::new_simple(login_creds),
+        &channel,
+        &message,
+    )
+    .await?;
+    match parts {
+        0 => println!("nothing to send to #{}", channel),
+        1 => println!("sent to #{}", channel),
+        parts => println!("sent to #{} in {} parts", channel, parts),
+    }
+    Ok(())
+}
 
-    let mut ndi_painter = NDIPainter::new()?;
-    let ndi_painter_handle = ndi_painter.run(frame_receiver);
+fn import_quotes(config: &Config, file: PathBuf) -> Result<()> {
+    let mut quotes = QuoteCommander::new(config.commands.quotes.clone())?;
+    let imported = quotes.import_yaml(&file)?;
+    println!(
+        "imported {} quotes from {} into {}",
+        imported,
+        file.display(),
+        config.commands.quotes.display()
+    );
+    Ok(())
+}
+
+fn export_quotes(config: &Config, file: PathBuf) -> Result<()> {
+    let quotes = QuoteCommander::new(config.commands.quotes.clone())?;
+    quotes.export_yaml(&file)?;
+    println!(
+        "exported quotes from {} to {}",
+        config.commands.quotes.display(),
+        file.display()
+    );
+    Ok(())
+}
 
+fn load_credentials(config: &Config) -> Result<StaticLoginCredentials> {
+    let mut file = File::open(&config.credentials)?;
+    let mut contents = String::new();
     file.read_to_string(&mut contents)?;
-    let login_creds: StaticLoginCredentials = serde_yaml::from_str(&contents)?;
+    Ok(serde_yaml::from_str(&contents)?)
+}
 
-    let config = ClientConfig::new_simple(login_creds);
-    let mut core = IrcCore::new();
-    let join_dispatcher = core.get_msg_dispatcher();
+fn load_commanders(config: &Config) -> Result<Commanders> {
+    let hard_coded_cmdr: Arc<dyn AsyncIrcCommander> = Arc::new(SyncCommander::new(
+        HardCodedCommander::new(config.commands.static_commands.display().to_string())?,
+    ));
+    let registry = CommandRegistry::new();
+    let custom_cmdr: Arc<dyn AsyncIrcCommander> = Arc::new(SyncCommander::new(
+        CustomCommander::new(config.commands.custom.clone())?.with_registry(registry.clone()),
+    ));
+    let counter_cmdr =
+        CounterCommander::new(config.commands.counters.clone())?.with_registry(registry.clone());
+    let counters_receiver = counter_cmdr.subscribe();
+    let counter_cmdr: Arc<dyn AsyncIrcCommander> = Arc::new(SyncCommander::new(counter_cmdr));
+    let quote_cmdr: Arc<dyn AsyncIrcCommander> = Arc::new(SyncCommander::new(QuoteCommander::new(
+        config.commands.quotes.clone(),
+    )?));
+    let mut channel_cmdr = ChannelCommander::new(config.channels.iter().map(|c| c.name.clone()));
+    if let Some(owner) = config.owner_channel() {
+        channel_cmdr = channel_cmdr.with_owner_channel(owner);
+    }
+    let channels_receiver = channel_cmdr.subscribe();
+    let channel_cmdr: Arc<dyn AsyncIrcCommander> = Arc::new(SyncCommander::new(channel_cmdr));
+    let commanders = vec![
+        hard_coded_cmdr,
+        custom_cmdr,
+        counter_cmdr,
+        quote_cmdr,
+        channel_cmdr,
+    ];
 
-    let mut chatbox_dispatcher =
-        ChatboxDispatcher::new(join_dispatcher.clone(), chatbox_state, event_loop_proxy);
-    let chatbox_dispatcher_handle = chatbox_dispatcher.run();
+    let names: Vec<&str> = commanders
+        .iter()
+        .map(|commander| commander.name())
+        .collect();
+    for channel in config.channels.iter() {
+        for name in channel.commanders.iter().flatten() {
+            if !names.contains(&name.as_str()) {
+                return Err(Error::InvalidConfig(format!(
+                    "channel \"{}\" enables unknown commander \"{}\", expected one of {}",
+                    channel.name,
+                    name,
+                    names.join(", ")
+                )));
+            }
+        }
+    }
 
-    let run_irc_handle = core.run_irc(config);
+    Ok(Commanders {
+        commanders,
+        counters: counters_receiver,
+        channels: channels_receiver,
+        registry,
+    })
+}
 
-    let cmdr_dispatcher = join_dispatcher.clone();
-    let hard_coded_cmdr = Box::new(HardCodedCommander::new("TODO".to_string()));
-    let mut cmdr_composer = CommanderComposer::new(cmdr_dispatcher, vec![hard_coded_cmdr]);
-    let cmdr_handle = cmdr_composer.run_commanders();
+/// Run a component that may be disabled.
+async fn run_optional<F: Future>(component: Option<F>) {
+    if let Some(component) = component {
+        component.await;
+    }
+}
 
-    let joiner_handler = tokio::spawn(async move {
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-        match join_dispatcher
-            .sender
-            .send(ComponentMessage::JoinChannel(JoinChannelMessage {
-                channel: "uuayn".to_string(),
-            })) {
-            Err(e) => {
-                println!("failed to join uuayn channel: {}", e)
-            }
-            _ => (),
+#[tokio::main]
+async fn all_the_async_things(
+    config: Config,
+    ui: Option<Ui>,
+    frame_receiver: Option<mpsc::UnboundedReceiver<NDIFrameData>>,
+) -> Result<()> {
+    let headless = ui.is_none();
+    let ndi_painter = match frame_receiver {
+        Some(frame_receiver) => {
+            Some((NDIPainter::new(&config.ndi.chatbox_source)?, frame_receiver))
         }
+        None => None,
+    };
+    let ndi_painter_handle = run_optional(ndi_painter.map(
+        |(mut ndi_painter, frame_receiver)| async move { ndi_painter.run(frame_receiver).await },
+    ));
+
+    let login_creds = load_credentials(&config)?;
+    let bot_login = login_creds.credentials.login.clone();
+    let mut core = IrcCore::new().with_queue_policy(config.queue.policy());
+    let channel_dispatcher = core.subscribe("channel manager");
+
+    let mut chatbox_dispatcher = ui.as_ref().map(|ui| {
+        ChatboxDispatcher::new(
+            channel_dispatcher.subscriber("chatbox"),
+            ui.chatbox_state.clone(),
+            ui.event_loop_proxy.clone(),
+        )
     });
+    let chatbox_dispatcher_handle = run_optional(
+        chatbox_dispatcher
+            .as_mut()
+            .map(|dispatcher| dispatcher.run()),
+    );
 
-    let (_, run_irc_result, joiner_result, _, _) = join5(
-        cmdr_handle,
-        run_irc_handle,
-        joiner_handler,
-        ndi_painter_handle,
-        chatbox_dispatcher_handle,
-    )
-    .await;
-    match joiner_result {
-        Err(e) => {
-            println!("joiner failed: {}", e)
+    let mut connection_dispatcher = ui.as_ref().map(|ui| {
+        ConnectionDispatcher::new(
+            channel_dispatcher.subscriber("connection status"),
+            ui.connection_state.clone(),
+            ui.event_loop_proxy.clone(),
+        )
+    });
+    let connection_dispatcher_handle = run_optional(
+        connection_dispatcher
+            .as_mut()
+            .map(|dispatcher| dispatcher.run()),
+    );
+
+    let run_irc_handle = core.run_irc(login_creds);
+
+    let cmdr_dispatcher = channel_dispatcher.subscriber("commander");
+    let commanders = load_commanders(&config)?;
+    let mut counters_dispatcher = ui.map(|ui| {
+        CountersDispatcher::new(commanders.counters, ui.counters_state, ui.event_loop_proxy)
+    });
+    let counters_dispatcher_handle = run_optional(
+        counters_dispatcher
+            .as_mut()
+            .map(|dispatcher| dispatcher.run()),
+    );
+    let mut cmdr_composer = CommanderComposer::new(cmdr_dispatcher, commanders.commanders)
+        .with_bot_login(&bot_login)
+        .with_registry(commanders.registry);
+    for channel in config.channels.iter() {
+        if let Some(prefixes) = &channel.prefixes {
+            cmdr_composer = cmdr_composer.with_prefixes(&channel.name, prefixes.clone());
         }
-        _ => (),
-    };
-    match run_irc_result {
-        Err(e) => {
-            println!("run_irc failed: {}", e)
+        if let Some(names) = &channel.commanders {
+            cmdr_composer = cmdr_composer.with_commanders(&channel.name, names.clone());
         }
-        _ => (),
+    }
+    if let Some(channel) = &config.commands.diagnostics_channel {
+        cmdr_composer = cmdr_composer.with_diagnostics_channel(channel.clone());
+    }
+    if let Some(triggers) = &config.commands.triggers {
+        cmdr_composer = cmdr_composer.with_triggers(Triggers::from_file(triggers)?);
+    }
+    let cmdr_handle = cmdr_composer.run_commanders();
+
+    let mut announcer = match &config.commands.announcements {
+        Some(announcements) => Some(Announcer::from_file(
+            channel_dispatcher.subscriber("announcer"),
+            announcements,
+        )?),
+        None => None,
     };
-    Ok(())
+    let announcer_handle = run_optional(announcer.as_mut().map(|announcer| announcer.run()));
+
+    // joins the channels on every login
+    let mut channel_manager = ChannelManager::new(channel_dispatcher, commanders.channels);
+    let channel_manager_handle = channel_manager.run();
+
+    // the components run for as long as the IRC core, which only gives up if the login is
+    // rejected
+    let components = async {
+        tokio::join!(
+            cmdr_handle,
+            announcer_handle,
+            channel_manager_handle,
+            ndi_painter_handle,
+            chatbox_dispatcher_handle,
+            counters_dispatcher_handle,
+            connection_dispatcher_handle,
+        )
+    };
+    tokio::pin!(components);
+    tokio::select! {
+        result = run_irc_handle => match result {
+            // the window keeps showing why the bot is disconnected until it's closed
+            Err(e) if !headless => {
+                println!("IRC core stopped: {}", e);
+                components.await;
+                Ok(())
+            }
+            result => result,
+        },
+        _ = &mut components => Ok(()),
+    }
 }
@@ -12,13 +12,13 @@ pub struct NDIPainter {
 }
 
 impl NDIPainter {
-    pub fn new() -> Result<Self> {
+    pub fn new(source_name: &str) -> Result<Self> {
         // set up NDI SDK for sending
         let instance = match load() {
             Ok(ndi) => ndi,
             Err(s) => return Err(Error::NDISDKError(s)),
         };
-        let sender = instance.create_send_instance("chatbox".to_string(), false, false)?;
+        let sender = instance.create_send_instance(source_name.to_string(), false, false)?;
         Ok(Self { sender })
     }
 
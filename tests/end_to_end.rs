@@ -0,0 +1,132 @@
+//! The bot against `FakeTwitchServer`, from logging in to answering commands.
+
+use std::sync::Arc;
+
+use twitch_irc::login::StaticLoginCredentials;
+
+use tmbf::channels::ChannelManager;
+use tmbf::commander::{
+    AsyncIrcCommander, ChannelCommander, CommanderComposer, HardCodedCommander, SyncCommander,
+};
+use tmbf::irc::{
+    next_connection_state, privmsg_line, ConnectionState, FakeTwitchServer, InMemoryTransport,
+    IrcCore, MessageDispatcher,
+};
+
+const BOT_LOGIN: &str = "botface";
+const STATIC_COMMANDS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/commands.yml");
+
+/// Start the bot with the hard coded and channel commanders, joining `channels`, the first of
+/// which is the owner's. Returns a subscriber for following its connection.
+fn start_bot(channels: &[&str]) -> MessageDispatcher {
+    let mut core = IrcCore::<InMemoryTransport>::with_transport();
+    let events = core.subscribe("test");
+
+    let channel_cmdr = ChannelCommander::new(channels.iter().map(|channel| channel.to_string()))
+        .with_owner_channel(channels[0]);
+    let joined = channel_cmdr.subscribe();
+    let hard_coded_cmdr = HardCodedCommander::new(STATIC_COMMANDS.to_string()).unwrap();
+    let commanders: Vec<Arc<dyn AsyncIrcCommander>> = vec![
+        Arc::new(SyncCommander::new(hard_coded_cmdr)),
+        Arc::new(SyncCommander::new(channel_cmdr)),
+    ];
+    let mut composer =
+        CommanderComposer::new(core.subscribe("commander"), commanders).with_bot_login(BOT_LOGIN);
+    let mut channel_manager = ChannelManager::new(core.subscribe("channel manager"), joined);
+
+    let credentials = StaticLoginCredentials::new(BOT_LOGIN.to_string(), Some("token".to_string()));
+    tokio::spawn(async move {
+        let (result, _, _) = tokio::join!(
+            core.run_irc(credentials),
+            composer.run_commanders(),
+            channel_manager.run()
+        );
+        result
+    });
+    events
+}
+
+async fn next_state(events: &mut MessageDispatcher) -> ConnectionState {
+    next_connection_state(events).await.unwrap()
+}
+
+#[tokio::test]
+async fn logs_in_and_joins_its_channels() {
+    let mut server = FakeTwitchServer::start();
+    let mut events = start_bot(&["uuayn", "other"]);
+
+    server.expect("PASS oauth:token").await.unwrap();
+    server.expect("NICK botface").await.unwrap();
+    assert_eq!(next_state(&mut events).await, ConnectionState::Connecting);
+    assert_eq!(next_state(&mut events).await, ConnectionState::Connected);
+    server
+        .expect_all(&["JOIN #uuayn", "JOIN #other"])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn answers_commands() {
+    let mut server = FakeTwitchServer::start();
+    let _events = start_bot(&["uuayn"]);
+
+    server
+        .run_script(&format!(
+            "< JOIN #uuayn\n> {}\n< PRIVMSG #uuayn :. woof\n> {}\n< PRIVMSG #uuayn :. enjoy the lurk!",
+            privmsg_line("viewer", "uuayn", "!meow"),
+            privmsg_line("viewer", "uuayn", "!lurk")
+        ))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn joins_and_parts_channels_on_command() {
+    let mut server = FakeTwitchServer::start();
+    let _events = start_bot(&["uuayn"]);
+    server.expect("JOIN #uuayn").await.unwrap();
+
+    // the reply and the JOIN are sent by different tasks
+    server
+        .send(&privmsg_line("uuayn", "uuayn", "!join other"))
+        .unwrap();
+    server
+        .expect_all(&["PRIVMSG #uuayn :. joining #other", "JOIN #other"])
+        .await
+        .unwrap();
+
+    // the PART waits for the reply queued before it
+    server
+        .run_script(&format!(
+            "> {}\n< PRIVMSG #other :. leaving #other\n< PART #other",
+            privmsg_line("other", "other", "!part")
+        ))
+        .await
+        .unwrap();
+    assert!(server
+        .send(&privmsg_line("other", "other", "!join other"))
+        .is_err());
+}
+
+#[tokio::test]
+async fn reconnects_when_asked_to() {
+    let mut server = FakeTwitchServer::start();
+    let mut events = start_bot(&["uuayn"]);
+    server.expect("JOIN #uuayn").await.unwrap();
+    assert_eq!(next_state(&mut events).await, ConnectionState::Connecting);
+    assert_eq!(next_state(&mut events).await, ConnectionState::Connected);
+
+    server.send(":tmi.twitch.tv RECONNECT").unwrap();
+    assert_eq!(
+        next_state(&mut events).await,
+        ConnectionState::Reconnecting { attempt: 1 }
+    );
+    server.expect("NICK botface").await.unwrap();
+    assert_eq!(next_state(&mut events).await, ConnectionState::Connected);
+    server.expect("JOIN #uuayn").await.unwrap();
+
+    server
+        .send(&privmsg_line("viewer", "uuayn", "!meow"))
+        .unwrap();
+    server.expect("PRIVMSG #uuayn :. woof").await.unwrap();
+}